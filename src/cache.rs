@@ -0,0 +1,93 @@
+use serde::{de::DeserializeOwned, Serialize};
+use std::{
+    fmt::Display,
+    fs::{read_to_string, rename, write},
+    io,
+    path::PathBuf,
+};
+use tracing::debug;
+
+use crate::{github::rate_limit::Budgets, model::Model};
+
+const MODEL_FILE: &str = "model.json";
+const GITHUB_BUDGETS_FILE: &str = "github-rate-limits.json";
+
+fn cache_dirs() -> Result<xdg::BaseDirectories, Error> {
+    xdg::BaseDirectories::with_prefix(env!("CARGO_PKG_NAME")).map_err(Error::XdgBaseDirectories)
+}
+
+/// Read the model saved by a previous scan, if any.
+pub fn read_model() -> Result<Option<Model>, Error> {
+    read_json(MODEL_FILE, "model")
+}
+
+/// Save the model, replacing the cache file only once fully written.
+pub fn write_model(model: &Model) -> Result<(), Error> {
+    write_json(MODEL_FILE, "model", model)
+}
+
+/// Read what was left of the GitHub rate limits when last used, if anything.
+pub fn read_github_budgets() -> Result<Option<Budgets>, Error> {
+    read_json(GITHUB_BUDGETS_FILE, "GitHub rate limits")
+}
+
+pub fn write_github_budgets(budgets: &Budgets) -> Result<(), Error> {
+    write_json(GITHUB_BUDGETS_FILE, "GitHub rate limits", budgets)
+}
+
+fn read_json<T>(file: &str, what: &str) -> Result<Option<T>, Error>
+where
+    T: DeserializeOwned,
+{
+    match cache_dirs()?.find_cache_file(file) {
+        Some(cache_path) => {
+            debug!("reading {} from {:?}", what, &cache_path);
+
+            let raw = read_to_string(&cache_path).map_err(Error::Io)?;
+            let value = serde_json::from_str(&raw).map_err(Error::Json)?;
+
+            Ok(Some(value))
+        }
+        None => {
+            debug!("no cached {}", what);
+            Ok(None)
+        }
+    }
+}
+
+/// Replace the cache file only once fully written.
+fn write_json<T>(file: &str, what: &str, value: &T) -> Result<(), Error>
+where
+    T: Serialize,
+{
+    let cache_path = cache_dirs()?.place_cache_file(file).map_err(Error::Io)?;
+    let mut tmp_path = cache_path.clone().into_os_string();
+    tmp_path.push(".tmp");
+    let tmp_path = PathBuf::from(tmp_path);
+
+    debug!("writing {} to {:?}", what, &cache_path);
+
+    let raw = serde_json::to_string(value).map_err(Error::Json)?;
+    write(&tmp_path, raw).map_err(Error::Io)?;
+    rename(&tmp_path, &cache_path).map_err(Error::Io)
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    Json(serde_json::Error),
+    XdgBaseDirectories(xdg::BaseDirectoriesError),
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use Error::*;
+        match self {
+            Io(e) => write!(f, "I/O error {}", e),
+            Json(e) => write!(f, "JSON error {}", e),
+            XdgBaseDirectories(e) => write!(f, "XDG error {}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
@@ -0,0 +1,606 @@
+use anyhow::{anyhow, bail, Result};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use serde::Serialize;
+use std::{
+    borrow::Cow,
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+use tracing::warn;
+
+use crate::{
+    cache,
+    config::Config,
+    model::{LocalRepo, Model, UpdateModel},
+    service::{
+        exec::{self, ExecCommand, ExecResult, ExecStatus, UpdateExec},
+        filesystem, Notify,
+    },
+};
+
+#[derive(Parser, Debug)]
+#[command(version, about)]
+pub struct Cli {
+    /// run non-interactively, otherwise start the TUI
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// List repo paths
+    List(CommandArgs),
+    /// List remotes of each repo
+    Remotes(CommandArgs),
+    /// Show status of each repo
+    Status(CommandArgs),
+    /// Run a command in each repo, with %f in its args substituted by the repo path
+    Exec(ExecArgs),
+}
+
+#[derive(Args, Debug)]
+pub struct CommandArgs {
+    /// output format
+    #[arg(short, long, value_enum, default_value_t = Format::Table)]
+    format: Format,
+
+    /// read the model cached by a previous scan instead of scanning
+    #[arg(short, long)]
+    cached: bool,
+
+    /// only repos whose path contains all of these words
+    filter: Vec<String>,
+}
+
+#[derive(Args, Debug)]
+pub struct ExecArgs {
+    #[command(flatten)]
+    common: CommandArgs,
+
+    /// maximum number of repos in which the command runs at once, overriding config
+    #[arg(short = 'j', long)]
+    concurrency: Option<usize>,
+
+    /// command and its args, following `--`
+    #[arg(last = true, required = true)]
+    command: Vec<String>,
+}
+
+#[derive(Clone, Copy, ValueEnum, Debug)]
+enum Format {
+    Table,
+    Json,
+    Ndjson,
+}
+
+impl Command {
+    fn args(&self) -> &CommandArgs {
+        use Command::*;
+        match self {
+            List(args) | Remotes(args) | Status(args) => args,
+            Exec(args) => &args.common,
+        }
+    }
+}
+
+pub async fn run(command: Command, config: &Config) -> Result<()> {
+    let args = command.args();
+    let model = if args.cached {
+        cache::read_model()?.unwrap_or_default()
+    } else {
+        scan(config).await
+    };
+    let filter = args.filter.join(" ");
+    let repos = model
+        .filtered(&filter)
+        .filter(|repo| !repo.vanished)
+        .collect::<Vec<_>>();
+
+    let mut out = io::stdout().lock();
+    match command {
+        Command::List(_) => write_records(&mut out, args.format, list(&repos)),
+        Command::Remotes(_) => write_records(&mut out, args.format, remotes(&repos)),
+        Command::Status(_) => write_records(&mut out, args.format, status(&repos)),
+        Command::Exec(ref exec_args) => {
+            // commands only run locally
+            let paths = repos
+                .iter()
+                .filter(|repo| repo.is_local())
+                .map(|repo| repo.path.clone())
+                .collect();
+            let results = run_exec(exec_args, paths, config).await?;
+            let n_failed = results.iter().filter(|result| !result.success()).count();
+
+            match args.format {
+                Format::Table => write_exec_results(&mut out, &results)?,
+                format => write_records(&mut out, format, exec_records(&results))?,
+            }
+
+            if n_failed > 0 {
+                Err(anyhow!("failed in {} of {} repos", n_failed, results.len()))
+            } else {
+                Ok(())
+            }
+        }
+    }
+}
+
+async fn run_exec(
+    args: &ExecArgs,
+    paths: Vec<PathBuf>,
+    config: &Config,
+) -> Result<Vec<ExecResult>> {
+    let command = ExecCommand {
+        command: args.command[0].clone(),
+        args: args.command[1..].to_vec(),
+        env: Vec::default(),
+    };
+    command.format_args("")?;
+
+    let mut exec_config = config.exec.clone();
+    if let Some(concurrency) = args.concurrency {
+        if concurrency == 0 {
+            bail!("concurrency must be at least 1");
+        }
+        exec_config.concurrency = concurrency;
+    }
+
+    let mut exec_service = exec::Service::new(&exec_config);
+    exec_service
+        .requester()
+        .send(exec::Request::Run(command, paths))
+        .unwrap();
+
+    let mut executed = Executed::default();
+    while let Some(ev) = exec_service.recv_event().await {
+        let completed = matches!(ev, exec::Event::Completed);
+        exec_service.handle_runs(ev, &mut executed).await;
+        if completed {
+            break;
+        }
+    }
+
+    let mut results = executed.0;
+    results.sort_by(|r0, r1| r0.path.cmp(&r1.path));
+    Ok(results)
+}
+
+/// Results of running a command, with messages going to stderr.
+#[derive(Default)]
+struct Executed(Vec<ExecResult>);
+
+impl UpdateExec for Executed {
+    fn exec_result(&mut self, result: ExecResult) {
+        self.0.push(result);
+    }
+
+    fn exec_completed(&mut self) {}
+}
+
+impl Notify for Executed {
+    fn info(&mut self, message: String) {
+        eprintln!("{}", message);
+    }
+
+    fn error(&mut self, message: String) {
+        eprintln!("error: {}", message);
+    }
+}
+
+/// Scan the filesystem to completion, refreshing the cache, and returning only local repos,
+/// since any from ssh hosts in the cache weren't scanned again.
+async fn scan(config: &Config) -> Model {
+    let mut filesystem_service = filesystem::Service::new(&config.filesystem);
+    filesystem_service
+        .requester()
+        .send(filesystem::Request::Scan)
+        .unwrap();
+
+    let mut scanned = Scanned(cache::read_model().ok().flatten().unwrap_or_default());
+    while let Some(ev) = filesystem_service.recv_event().await {
+        let completed = matches!(ev, filesystem::Event::ScanCompleted);
+        filesystem_service.handle(ev, &mut scanned).await;
+        if completed {
+            break;
+        }
+    }
+
+    if let Err(e) = cache::write_model(&scanned.0) {
+        warn!("failed to write model cache: {}", e);
+    }
+    scanned.0.repos.retain(|id, _| id.is_local());
+    scanned.0
+}
+
+/// Model being built by a scan, with messages going to stderr.
+struct Scanned(Model);
+
+impl UpdateModel for Scanned {
+    fn add_local_repo(&mut self, repo: LocalRepo) {
+        self.0.add_local_repo(repo);
+    }
+
+    fn remove_local_repos(&mut self, path: &Path) {
+        self.0.remove_local_repos(path);
+    }
+
+    fn scan_started(&mut self, host: Option<&str>) {
+        self.0.scan_started(host);
+    }
+
+    fn scan_completed(&mut self, host: Option<&str>) {
+        self.0.scan_completed(host);
+    }
+
+    fn scan_failed(&mut self, host: Option<&str>) {
+        self.0.scan_failed(host);
+    }
+}
+
+impl Notify for Scanned {
+    fn info(&mut self, message: String) {
+        eprintln!("{}", message);
+    }
+
+    fn error(&mut self, message: String) {
+        eprintln!("error: {}", message);
+    }
+}
+
+/// A row of output, as either a table row or a JSON object.
+trait Record: Serialize {
+    const HEADINGS: &'static [&'static str];
+
+    fn cells(&self) -> Vec<String>;
+}
+
+#[derive(Serialize)]
+struct RepoRecord<'a> {
+    /// ssh host, or none for a local repo
+    host: Option<&'a str>,
+    path: Cow<'a, str>,
+}
+
+impl Record for RepoRecord<'_> {
+    const HEADINGS: &'static [&'static str] = &["PATH"];
+
+    fn cells(&self) -> Vec<String> {
+        vec![host_path(self.host, &self.path)]
+    }
+}
+
+/// `host:path` for repos on ssh hosts, as in the TUI.
+fn host_path(host: Option<&str>, path: &str) -> String {
+    match host {
+        Some(host) => format!("{}:{}", host, path),
+        None => path.to_string(),
+    }
+}
+
+fn list<'a>(repos: &[&'a LocalRepo]) -> Vec<RepoRecord<'a>> {
+    repos
+        .iter()
+        .map(|repo| RepoRecord {
+            host: repo.host.as_deref(),
+            path: repo.path.to_string_lossy(),
+        })
+        .collect()
+}
+
+#[derive(Serialize)]
+struct RemoteRecord<'a> {
+    /// ssh host, or none for a local repo
+    host: Option<&'a str>,
+    path: Cow<'a, str>,
+    name: &'a str,
+    fetch_url: Option<&'a str>,
+    push_url: Option<&'a str>,
+    /// canonical `host/owner/repo`
+    identity: Option<String>,
+}
+
+impl Record for RemoteRecord<'_> {
+    const HEADINGS: &'static [&'static str] = &["PATH", "REMOTE", "FETCH URL", "PUSH URL"];
+
+    fn cells(&self) -> Vec<String> {
+        vec![
+            host_path(self.host, &self.path),
+            self.name.to_string(),
+            self.fetch_url.unwrap_or("-").to_string(),
+            self.push_url.unwrap_or("-").to_string(),
+        ]
+    }
+}
+
+fn remotes<'a>(repos: &[&'a LocalRepo]) -> Vec<RemoteRecord<'a>> {
+    repos
+        .iter()
+        .flat_map(|repo| {
+            repo.remotes.iter().map(|remote| RemoteRecord {
+                host: repo.host.as_deref(),
+                path: repo.path.to_string_lossy(),
+                name: remote.name(),
+                fetch_url: remote.fetch_url(),
+                push_url: remote.push_url(),
+                identity: remote.identity().map(|identity| identity.to_string()),
+            })
+        })
+        .collect()
+}
+
+#[derive(Serialize)]
+struct StatusRecord<'a> {
+    /// ssh host, or none for a local repo
+    host: Option<&'a str>,
+    path: Cow<'a, str>,
+    head: String,
+    dirty: bool,
+    untracked: bool,
+    stashes: usize,
+    ahead: Option<usize>,
+    behind: Option<usize>,
+    /// compact form as shown in the TUI
+    summary: String,
+}
+
+impl Record for StatusRecord<'_> {
+    const HEADINGS: &'static [&'static str] = &["PATH", "STATUS"];
+
+    fn cells(&self) -> Vec<String> {
+        vec![host_path(self.host, &self.path), self.summary.clone()]
+    }
+}
+
+fn status<'a>(repos: &[&'a LocalRepo]) -> Vec<StatusRecord<'a>> {
+    repos
+        .iter()
+        .map(|repo| StatusRecord {
+            host: repo.host.as_deref(),
+            path: repo.path.to_string_lossy(),
+            head: repo.status.head.to_string(),
+            dirty: repo.status.dirty,
+            untracked: repo.status.untracked,
+            stashes: repo.status.stashes,
+            ahead: repo.status.ahead_behind.map(|(ahead, _)| ahead),
+            behind: repo.status.ahead_behind.map(|(_, behind)| behind),
+            summary: repo.status.to_string(),
+        })
+        .collect()
+}
+
+#[derive(Serialize)]
+struct ExecRecord<'a> {
+    path: Cow<'a, str>,
+    /// None if the command was killed or couldn't be run
+    exit_code: Option<i32>,
+    status: String,
+    stdout: &'a str,
+    stderr: &'a str,
+}
+
+impl Record for ExecRecord<'_> {
+    const HEADINGS: &'static [&'static str] = &["PATH", "STATUS"];
+
+    fn cells(&self) -> Vec<String> {
+        vec![self.path.to_string(), self.status.clone()]
+    }
+}
+
+fn exec_records(results: &[ExecResult]) -> Vec<ExecRecord<'_>> {
+    results
+        .iter()
+        .map(|result| ExecRecord {
+            path: result.path.to_string_lossy(),
+            exit_code: match result.status {
+                ExecStatus::Exited(code) => Some(code),
+                _ => None,
+            },
+            status: result.status.to_string(),
+            stdout: &result.stdout,
+            stderr: &result.stderr,
+        })
+        .collect()
+}
+
+/// Each repo's output under a heading, in the manner of `mr`.
+fn write_exec_results<W>(out: &mut W, results: &[ExecResult]) -> io::Result<()>
+where
+    W: Write,
+{
+    for result in results {
+        writeln!(
+            out,
+            "==> {} ({})",
+            result.path.to_string_lossy(),
+            result.status
+        )?;
+        for output in [&result.stdout, &result.stderr] {
+            out.write_all(output.as_bytes())?;
+            if !output.is_empty() && !output.ends_with('\n') {
+                writeln!(out)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn write_records<W, R>(out: &mut W, format: Format, records: Vec<R>) -> Result<()>
+where
+    W: Write,
+    R: Record,
+{
+    match format {
+        Format::Table => {
+            let rows = records.iter().map(Record::cells).collect::<Vec<_>>();
+            let mut widths = R::HEADINGS
+                .iter()
+                .map(|heading| heading.chars().count())
+                .collect::<Vec<_>>();
+            for row in rows.iter() {
+                for (width, cell) in widths.iter_mut().zip(row) {
+                    *width = (*width).max(cell.chars().count());
+                }
+            }
+
+            let headings = R::HEADINGS.iter().map(|heading| heading.to_string());
+            write_row(out, &widths, headings)?;
+            for row in rows {
+                write_row(out, &widths, row)?;
+            }
+        }
+        Format::Json => {
+            serde_json::to_writer_pretty(&mut *out, &records)?;
+            writeln!(out)?;
+        }
+        Format::Ndjson => {
+            for record in records {
+                serde_json::to_writer(&mut *out, &record)?;
+                writeln!(out)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn write_row<W, I>(out: &mut W, widths: &[usize], cells: I) -> io::Result<()>
+where
+    W: Write,
+    I: IntoIterator<Item = String>,
+{
+    let cells = cells.into_iter().collect::<Vec<_>>();
+    let last = cells.len().saturating_sub(1);
+    for (i, (cell, width)) in cells.iter().zip(widths).enumerate() {
+        if i == last {
+            // no trailing padding
+            writeln!(out, "{}", cell)?;
+        } else {
+            write!(out, "{:width$}  ", cell, width = width)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+    use test_case::test_case;
+
+    use super::*;
+    use crate::model::{Head, Remote, RepoStatus};
+
+    fn repos() -> Vec<LocalRepo> {
+        let status = RepoStatus {
+            head: Head::Branch("main".to_string()),
+            dirty: true,
+            untracked: false,
+            stashes: 1,
+            ahead_behind: Some((2, 0)),
+        };
+        let remote = Remote::new(
+            "origin",
+            Some("git@github.com:acme/widget.git".to_string()),
+            Some("git@github.com:acme/widget.git".to_string()),
+        );
+        vec![
+            LocalRepo::new(PathBuf::from("/src/widget"), vec![remote], status),
+            LocalRepo::new(
+                PathBuf::from("srv/gadget"),
+                Vec::default(),
+                RepoStatus::default(),
+            )
+            .on_host("server"),
+        ]
+    }
+
+    fn output<R>(format: Format, records: Vec<R>) -> String
+    where
+        R: Record,
+    {
+        let mut out = Vec::new();
+        write_records(&mut out, format, records).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    /// The records as JSON, and as NDJSON, which must agree.
+    fn json_records<R>(records: impl Fn() -> Vec<R>) -> Value
+    where
+        R: Record,
+    {
+        let json = serde_json::from_str::<Value>(&output(Format::Json, records())).unwrap();
+        let ndjson = output(Format::Ndjson, records())
+            .lines()
+            .map(|line| serde_json::from_str::<Value>(line).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(json, Value::Array(ndjson));
+        json
+    }
+
+    #[test]
+    fn test_list_json() {
+        let repos = repos();
+        let repos = repos.iter().collect::<Vec<_>>();
+        assert_eq!(
+            json_records(|| list(&repos)),
+            json!([
+                { "host": null, "path": "/src/widget" },
+                { "host": "server", "path": "srv/gadget" },
+            ])
+        );
+    }
+
+    #[test]
+    fn test_remotes_json() {
+        let repos = repos();
+        let repos = repos.iter().collect::<Vec<_>>();
+        assert_eq!(
+            json_records(|| remotes(&repos)),
+            json!([{
+                "host": null,
+                "path": "/src/widget",
+                "name": "origin",
+                "fetch_url": "git@github.com:acme/widget.git",
+                "push_url": "git@github.com:acme/widget.git",
+                "identity": "github.com/acme/widget",
+            }])
+        );
+    }
+
+    #[test]
+    fn test_status_json() {
+        let repos = repos();
+        let repos = repos.iter().collect::<Vec<_>>();
+        assert_eq!(
+            json_records(|| status(&repos)),
+            json!([
+                {
+                    "host": null,
+                    "path": "/src/widget",
+                    "head": "main",
+                    "dirty": true,
+                    "untracked": false,
+                    "stashes": 1,
+                    "ahead": 2,
+                    "behind": 0,
+                    "summary": "main * ↑2 $1",
+                },
+                {
+                    "host": "server",
+                    "path": "srv/gadget",
+                    "head": "?",
+                    "dirty": false,
+                    "untracked": false,
+                    "stashes": 0,
+                    "ahead": null,
+                    "behind": null,
+                    "summary": "?",
+                },
+            ])
+        );
+    }
+
+    #[test_case(Format::Json, "[]\n"; "json")]
+    #[test_case(Format::Ndjson, ""; "ndjson")]
+    fn test_no_records(format: Format, expected: &str) {
+        assert_eq!(output(format, list(&[])), expected);
+    }
+}
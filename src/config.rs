@@ -12,6 +12,9 @@ pub struct Config {
     pub filesystem: FilesystemConfig,
     pub view: ViewConfig,
     pub git_client: GitClientConfig,
+    pub exec: ExecConfig,
+    pub ssh: SshConfig,
+    pub github: GitHubConfig,
 }
 
 #[derive(Clone, Default, Deserialize, Debug)]
@@ -51,11 +54,132 @@ impl GitClientConfig {
     where
         S: AsRef<str>,
     {
-        let template_params = [('f', f)].into_iter().collect::<HashMap<_, _>>();
-        self.args
+        template::format_path_args(&self.args, f.as_ref()).map_err(Error::GitClientTemplate)
+    }
+}
+
+#[derive(Clone, Deserialize, Debug)]
+#[serde(default)]
+#[serde(rename_all = "kebab-case")]
+pub struct ExecConfig {
+    /// maximum number of repos in which a command runs at once
+    pub concurrency: usize,
+}
+
+impl Default for ExecConfig {
+    fn default() -> Self {
+        Self { concurrency: 8 }
+    }
+}
+
+#[derive(Clone, Default, Deserialize, Debug)]
+#[serde(default)]
+#[serde(rename_all = "kebab-case")]
+pub struct SshConfig {
+    /// ask whether to trust host keys not in known_hosts, rather than refusing them,
+    /// except for hosts known only by `@cert-authority`, which are always refused
+    pub trust_on_first_use: bool,
+    pub hosts: Vec<SshHostConfig>,
+}
+
+/// A host whose repos are found by traversing its filesystem over SFTP.
+#[derive(Clone, Deserialize, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub struct SshHostConfig {
+    /// overriding any from the ssh config
+    #[serde(default)]
+    pub user: Option<String>,
+    /// as known to ssh, so may be an alias from the ssh config
+    pub host: String,
+    /// where `~` is the home directory on the host, as in `excludes`
+    pub roots: Vec<String>,
+    /// globs matched against paths on the host, so only expanded once its home directory is known
+    #[serde(default)]
+    pub excludes: Vec<String>,
+    /// how far below each root to look for repos
+    #[serde(default)]
+    pub max_depth: Option<usize>,
+    /// after keys, try keyboard-interactive and password authentication, prompting in the TUI
+    #[serde(default)]
+    pub password_auth: bool,
+    /// maximum number of SFTP requests awaiting a reply at once
+    #[serde(default = "default_max_in_flight")]
+    pub max_in_flight: usize,
+}
+
+#[derive(Clone, Default, Deserialize, Debug)]
+#[serde(default)]
+#[serde(rename_all = "kebab-case")]
+pub struct GitHubConfig {
+    /// of the API, for GitHub Enterprise, e.g. `https://github.example.com/api/v3`
+    pub base_url: Option<String>,
+    /// used only if `GITHUB_TOKEN` is unset, taking precedence over `gh` and git credential helpers
+    pub token: Option<String>,
+    /// whose public repos are listed when there's no token
+    pub user: Option<String>,
+    /// where repos are cloned, with `%h`, `%o` and `%r` substituted by host, owner and repo,
+    /// e.g. `~/src/%h/%o/%r`
+    pub clone_path: Option<String>,
+    pub clone_url: CloneUrl,
+}
+
+impl GitHubConfig {
+    /// Where to clone the repo, if configured.
+    pub fn clone_path(
+        &self,
+        host: &str,
+        owner: &str,
+        repo: &str,
+    ) -> Result<Option<PathBuf>, Error> {
+        let Some(clone_path) = self.clone_path.as_ref() else {
+            return Ok(None);
+        };
+        let template_params = [('h', host), ('o', owner), ('r', repo)]
+            .into_iter()
+            .collect::<HashMap<_, _>>();
+        let path =
+            template::format(clone_path, &template_params).map_err(Error::ClonePathTemplate)?;
+        Ok(Some(PathBuf::from(shellexpand::tilde(&path).as_ref())))
+    }
+}
+
+/// Which of a GitHub repo's URLs to clone from.
+#[derive(Clone, Copy, Default, Deserialize, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub enum CloneUrl {
+    #[default]
+    Https,
+    Ssh,
+}
+
+impl SshHostConfig {
+    /// Roots with `~` expanded to the home directory on the host.
+    pub fn roots_on_host(&self, home: &str) -> Vec<String> {
+        self.roots
             .iter()
-            .map(|arg| template::format(arg, &template_params).map_err(Error::GitClientTemplate))
-            .collect::<Result<Vec<_>, Error>>()
+            .map(|root| expand_remote_tilde(root, home))
+            .collect()
+    }
+
+    /// Excludes with `~` expanded to the home directory on the host.
+    pub fn excludes_on_host(&self, home: &str) -> Result<GlobSet, Error> {
+        let invalid = |e| Error::SshExcludes(self.host.clone(), e);
+        let mut globset_builder = GlobSet::builder();
+        for glob_str in self.excludes.iter() {
+            globset_builder.add(Glob::new(&expand_remote_tilde(glob_str, home)).map_err(invalid)?);
+        }
+        globset_builder.build().map_err(invalid)
+    }
+}
+
+/// The path with a leading `~` replaced by `home`, a path on a remote host.
+fn expand_remote_tilde(path: &str, home: &str) -> String {
+    if path == "~" {
+        home.trim_end_matches('/').to_string()
+    } else if let Some(rest) = path.strip_prefix("~/") {
+        format!("{}/{}", home.trim_end_matches('/'), rest)
+    } else {
+        path.to_string()
     }
 }
 
@@ -63,6 +187,10 @@ fn default_collapse_paths() -> bool {
     true
 }
 
+fn default_max_in_flight() -> usize {
+    16
+}
+
 fn deserialize_globset<'de, D>(deserializer: D) -> Result<GlobSet, D::Error>
 where
     D: Deserializer<'de>,
@@ -103,6 +231,23 @@ fn valid_config(c: Config) -> Result<Config, Error> {
     }
 
     c.git_client.format_args("dummy/path")?;
+    c.github.clone_path("dummy", "dummy", "dummy")?;
+
+    if c.exec.concurrency == 0 {
+        Err(Error::ZeroExecConcurrency)?
+    }
+
+    if let Some(host) = c.ssh.hosts.iter().find(|host| host.roots.is_empty()) {
+        Err(Error::EmptySshRoots(host.host.clone()))?
+    }
+
+    if let Some(host) = c.ssh.hosts.iter().find(|host| host.max_in_flight == 0) {
+        Err(Error::ZeroSshMaxInFlight(host.host.clone()))?
+    }
+
+    for host in c.ssh.hosts.iter() {
+        host.excludes_on_host("/dummy")?;
+    }
 
     Ok(c)
 }
@@ -114,6 +259,11 @@ pub enum Error {
     XdgBaseDirectories(xdg::BaseDirectoriesError),
     EmptyFilesystemScannerRoots,
     GitClientTemplate(template::Error),
+    ClonePathTemplate(template::Error),
+    ZeroExecConcurrency,
+    EmptySshRoots(String),
+    ZeroSshMaxInFlight(String),
+    SshExcludes(String, globset::Error),
 }
 
 impl Display for Error {
@@ -125,8 +275,48 @@ impl Display for Error {
             XdgBaseDirectories(e) => write!(f, "XDG error {}", e),
             EmptyFilesystemScannerRoots => f.write_str("missing filesystem scanner roots"),
             GitClientTemplate(e) => write!(f, "Git client template error {}", e),
+            ClonePathTemplate(e) => write!(f, "GitHub clone path template error {}", e),
+            ZeroExecConcurrency => f.write_str("exec concurrency must be at least 1"),
+            EmptySshRoots(host) => write!(f, "missing ssh roots for {}", host),
+            ZeroSshMaxInFlight(host) => {
+                write!(f, "ssh max-in-flight for {} must be at least 1", host)
+            }
+            SshExcludes(host, e) => write!(f, "ssh excludes error for {} {}", host, e),
         }
     }
 }
 
 impl std::error::Error for Error {}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+
+    #[test_case("~", "/home/me"; "home")]
+    #[test_case("~/.cache/**", "/home/me/.cache/**"; "below home")]
+    #[test_case("/srv/git", "/srv/git"; "absolute")]
+    #[test_case("src", "src"; "relative")]
+    #[test_case("~other/src", "~other/src"; "other user")]
+    fn test_expand_remote_tilde(path: &str, expected: &str) {
+        assert_eq!(expand_remote_tilde(path, "/home/me/"), expected);
+    }
+
+    #[test]
+    fn test_ssh_excludes_on_host() {
+        let host = SshHostConfig {
+            user: None,
+            host: "server".to_string(),
+            roots: vec!["~".to_string()],
+            excludes: vec!["~/.cache/**".to_string()],
+            max_depth: None,
+            password_auth: false,
+            max_in_flight: default_max_in_flight(),
+        };
+        assert_eq!(host.roots_on_host("/home/me"), vec!["/home/me"]);
+        let excludes = host.excludes_on_host("/home/me").unwrap();
+        assert!(excludes.is_match("/home/me/.cache/pip"));
+        assert!(!excludes.is_match("/home/me/src/widget"));
+    }
+}
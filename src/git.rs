@@ -0,0 +1,931 @@
+use anyhow::{anyhow, Context, Result};
+use gix::{
+    bstr::{BStr, ByteSlice},
+    head::Kind,
+    refs::{
+        transaction::{Change, LogChange, PreviousValue, RefEdit, RefLog},
+        FullName, Target,
+    },
+    remote::Direction,
+    Repository,
+};
+use std::{
+    borrow::{Borrow, Cow},
+    collections::{BinaryHeap, HashMap, HashSet},
+    fs::{remove_file, rename, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+};
+use tracing::{debug, warn};
+
+use crate::model::{Head, LocalRepo, Remote, RemoteEdit, RepoStatus, UrlRewrite};
+
+/// Read remotes and status of the repo at `path`, blocking.
+pub fn local_repo(path: PathBuf) -> LocalRepo {
+    match gix::discover(&path) {
+        Ok(repo) => {
+            let remotes = remotes(&repo);
+            let status = status(&repo);
+            LocalRepo::new(path, remotes, status)
+        }
+        Err(e) => {
+            warn!("git discover failed on {:?}: {}", &path, e);
+            LocalRepo::new(path, Vec::default(), RepoStatus::default())
+        }
+    }
+}
+
+fn remotes(repo: &Repository) -> Vec<Remote> {
+    let config = repo.config_snapshot();
+    repo.remote_names()
+        .into_iter()
+        .filter_map(|name| {
+            let name: &BStr = name.borrow();
+            match repo.find_remote(name) {
+                Ok(remote) => {
+                    let url = |direction| {
+                        remote
+                            .url(direction)
+                            .map(|url| url.to_bstring().to_string())
+                    };
+                    let configured = |key| {
+                        config
+                            .plumbing()
+                            .string_by("remote", Some(name), key)
+                            .map(|value| value.to_string())
+                    };
+                    Some(
+                        Remote::new(name, url(Direction::Fetch), url(Direction::Push))
+                            .with_configured(configured("url"), configured("pushurl")),
+                    )
+                }
+                Err(e) => {
+                    warn!("find_remote {} failed on {:?}: {}", name, repo.path(), e);
+                    None
+                }
+            }
+        })
+        .collect::<Vec<_>>()
+}
+
+fn status(repo: &Repository) -> RepoStatus {
+    let path = repo.path();
+    let (dirty, untracked) = worktree_changes(repo).unwrap_or_else(|e| {
+        warn!("worktree status failed on {:?}: {}", path, e);
+        (false, false)
+    });
+
+    RepoStatus {
+        head: head(repo).unwrap_or_else(|e| {
+            warn!("head failed on {:?}: {}", path, e);
+            Head::Unknown
+        }),
+        dirty,
+        untracked,
+        stashes: stashes(repo).unwrap_or_else(|e| {
+            warn!("stash count failed on {:?}: {}", path, e);
+            0
+        }),
+        ahead_behind: ahead_behind(repo).unwrap_or_else(|e| {
+            warn!("ahead/behind failed on {:?}: {}", path, e);
+            None
+        }),
+    }
+}
+
+fn head(repo: &Repository) -> Result<Head> {
+    Ok(match repo.head()?.kind {
+        Kind::Symbolic(reference) => Head::Branch(reference.name.shorten().to_string()),
+        Kind::Unborn(name) => Head::Unborn(name.shorten().to_string()),
+        Kind::Detached { target, .. } => Head::Detached(target.to_hex_with_len(7).to_string()),
+    })
+}
+
+/// Return whether tracked files have changed, and whether there are untracked files,
+/// stopping as soon as both are known.
+fn worktree_changes(repo: &Repository) -> Result<(bool, bool)> {
+    use gix::status::plumbing::index_as_worktree_with_renames::Summary;
+
+    let (mut dirty, mut untracked) = (false, false);
+    for item in repo
+        .status(gix::progress::Discard)?
+        .index_worktree_rewrites(None)
+        .into_index_worktree_iter(Vec::new())?
+    {
+        match item?.summary() {
+            Some(Summary::Added) => untracked = true,
+            Some(_) => dirty = true,
+            None => (),
+        }
+        if dirty && untracked {
+            break;
+        }
+    }
+
+    Ok((dirty, untracked))
+}
+
+fn stashes(repo: &Repository) -> Result<usize> {
+    Ok(match repo.try_find_reference("refs/stash")? {
+        Some(stash) => stash
+            .log_iter()
+            .all()?
+            .map(|entries| entries.count())
+            .unwrap_or(0),
+        None => 0,
+    })
+}
+
+/// Count commits reachable from HEAD but not its upstream, and vice versa.
+fn ahead_behind(repo: &Repository) -> Result<Option<(usize, usize)>> {
+    let Some(head_ref) = repo.head_ref()? else {
+        return Ok(None);
+    };
+    let Some(upstream_name) =
+        repo.branch_remote_tracking_ref_name(head_ref.name(), Direction::Fetch)
+    else {
+        return Ok(None);
+    };
+    let Some(mut upstream_ref) = repo.try_find_reference(upstream_name?.as_ref())? else {
+        return Ok(None);
+    };
+
+    let counts = count_exclusive(
+        repo,
+        repo.head_id()?.detach(),
+        upstream_ref.peel_to_id_in_place()?.detach(),
+    )?;
+    Ok(Some(counts))
+}
+
+/// Reachable from `left` only.
+const LEFT: u8 = 1;
+/// Reachable from `right` only.
+const RIGHT: u8 = 2;
+const BOTH: u8 = LEFT | RIGHT;
+
+/// How many commits are reachable from `left` but not `right`, and vice versa.
+///
+/// Rather than walking all history, both sides are walked together, newest first, marking which
+/// sides reach each commit, until all commits left to walk are reachable from both, so below the
+/// merge bases. Commits found reachable from the other side only after being walked are walked
+/// again to mark their ancestors too. As with git's merge bases, clock skew may miscount.
+fn count_exclusive(
+    repo: &Repository,
+    left: gix::ObjectId,
+    right: gix::ObjectId,
+) -> Result<(usize, usize)> {
+    let mut reached = HashMap::from([(left, LEFT)]);
+    *reached.entry(right).or_insert(0) |= RIGHT;
+    let mut queue = BinaryHeap::new();
+    let mut queued = HashSet::new();
+    let mut walked = HashSet::new();
+    // queued commits still to walk, those not reached from both sides or walked already
+    let mut n_pending = 0;
+    for (id, sides) in reached.iter() {
+        queue.push((commit_time(repo, *id)?, *id));
+        queued.insert(*id);
+        if *sides != BOTH {
+            n_pending += 1;
+        }
+    }
+
+    while n_pending > 0 {
+        // not empty, as something is pending
+        let (_, id) = queue.pop().unwrap();
+        queued.remove(&id);
+        let sides = reached[&id];
+        if sides != BOTH || walked.contains(&id) {
+            n_pending -= 1;
+        }
+        walked.insert(id);
+        for parent_id in repo.find_commit(id)?.parent_ids() {
+            let parent_id = parent_id.detach();
+            let parent_sides = reached.entry(parent_id).or_insert(0);
+            if *parent_sides | sides == *parent_sides {
+                continue;
+            }
+            *parent_sides |= sides;
+            let is_pending = *parent_sides != BOTH || walked.contains(&parent_id);
+            if queued.insert(parent_id) {
+                queue.push((commit_time(repo, parent_id)?, parent_id));
+                if is_pending {
+                    n_pending += 1;
+                }
+            } else if !is_pending {
+                // was pending when queued, not being reached from both sides then
+                n_pending -= 1;
+            }
+        }
+    }
+
+    Ok(reached
+        .values()
+        .fold((0, 0), |(n_left, n_right), sides| match *sides {
+            LEFT => (n_left + 1, n_right),
+            RIGHT => (n_left, n_right + 1),
+            _ => (n_left, n_right),
+        }))
+}
+
+fn commit_time(repo: &Repository, id: gix::ObjectId) -> Result<i64> {
+    Ok(repo.find_commit(id)?.time()?.seconds)
+}
+
+/// Apply `rewrite` to every configured url and pushurl of every remote of the repo at `path`,
+/// blocking, and returning how many values were changed.
+pub fn rewrite_remote_urls(path: &Path, rewrite: &UrlRewrite) -> Result<usize> {
+    let mut config = LocalConfig::open(path)?;
+    let remote_names = config.remote_names();
+    let mut n_rewritten = 0;
+
+    for remote_name in remote_names.iter() {
+        for key in ["url", "pushurl"] {
+            if let Ok(mut values) =
+                config
+                    .file
+                    .raw_values_mut_by("remote", Some(remote_name.as_str().into()), key)
+            {
+                let rewritten = values
+                    .get()?
+                    .iter()
+                    .map(|value| rewrite.apply(&value.to_string()))
+                    .collect::<Vec<_>>();
+                for (i, value) in rewritten.into_iter().enumerate() {
+                    if let Some(value) = value {
+                        debug!("rewriting remote.{}.{} to {}", remote_name, key, &value);
+                        values.set_string_at(i, value);
+                        n_rewritten += 1;
+                    }
+                }
+            }
+        }
+    }
+
+    if n_rewritten > 0 {
+        config.write()?;
+    }
+
+    Ok(n_rewritten)
+}
+
+/// Make a single change to the remotes of the repo at `path`, blocking.
+///
+/// As with `git remote`, renaming or removing a remote also updates the branches
+/// which track it, and its remote-tracking refs.
+pub fn edit_remote(path: &Path, edit: &RemoteEdit) -> Result<()> {
+    use RemoteEdit::*;
+
+    let mut config = LocalConfig::open(path)?;
+    match edit {
+        Add { name, url } => {
+            check_remote_name(name)?;
+            config.check_remote(name, false)?;
+            let mut section = config
+                .file
+                .new_section("remote", Some(Cow::Owned(name.as_str().into())))?;
+            section.push("url".try_into()?, Some(url.as_str().into()));
+            section.push(
+                "fetch".try_into()?,
+                Some(
+                    format!("+refs/heads/*:{}*", remote_refs_prefix(name))
+                        .as_str()
+                        .into(),
+                ),
+            );
+            config.write()
+        }
+
+        Rename { name, new_name } => {
+            config.check_remote(name, true)?;
+            check_remote_name(new_name)?;
+            config.check_remote(new_name, false)?;
+            // renaming a section doesn't update the lookup of sections by name, hence the reparse
+            while config
+                .file
+                .rename_section(
+                    "remote",
+                    Some(name.as_str().into()),
+                    "remote",
+                    Some(Cow::Owned(new_name.as_str().into())),
+                )
+                .is_ok()
+            {
+                config.reparse()?;
+            }
+            if let Ok(mut fetch_specs) =
+                config
+                    .file
+                    .raw_values_mut_by("remote", Some(new_name.as_str().into()), "fetch")
+            {
+                let renamed = fetch_specs
+                    .get()?
+                    .iter()
+                    .map(|spec| {
+                        spec.to_string()
+                            .replace(&remote_refs_prefix(name), &remote_refs_prefix(new_name))
+                    })
+                    .collect::<Vec<_>>();
+                for (i, spec) in renamed.into_iter().enumerate() {
+                    fetch_specs.set_string_at(i, spec);
+                }
+            }
+            for branch in config.branches_tracking(name) {
+                config.file.set_raw_value_by(
+                    "branch",
+                    Some(branch.as_str().into()),
+                    "remote",
+                    new_name.as_str(),
+                )?;
+            }
+            // refs first, since failing to move them leaves the remote still usable
+            move_remote_refs(path, name, Some(new_name))?;
+            config.write()
+        }
+
+        Remove { name } => {
+            config.check_remote(name, true)?;
+            while config
+                .file
+                .remove_section("remote", Some(name.as_str().into()))
+                .is_some()
+            {}
+            for branch in config.branches_tracking(name) {
+                if let Ok(mut section) = config
+                    .file
+                    .section_mut("branch", Some(branch.as_str().into()))
+                {
+                    while section.remove("remote").is_some() {}
+                    while section.remove("merge").is_some() {}
+                }
+            }
+            move_remote_refs(path, name, None)?;
+            config.write()
+        }
+
+        SetUrl { name, url } => {
+            config.check_remote(name, true)?;
+            config.file.set_raw_value_by(
+                "remote",
+                Some(name.as_str().into()),
+                "url",
+                url.as_str(),
+            )?;
+            config.write()
+        }
+
+        SetPushUrl { name, url } => {
+            config.check_remote(name, true)?;
+            match url {
+                Some(url) => {
+                    config.file.set_raw_value_by(
+                        "remote",
+                        Some(name.as_str().into()),
+                        "pushurl",
+                        url.as_str(),
+                    )?;
+                }
+                None => {
+                    if let Ok(mut section) = config
+                        .file
+                        .section_mut("remote", Some(name.as_str().into()))
+                    {
+                        while section.remove("pushurl").is_some() {}
+                    }
+                }
+            }
+            config.write()
+        }
+    }
+}
+
+/// Fail unless `name` makes valid remote-tracking ref names, as git requires of remote names.
+fn check_remote_name(name: &str) -> Result<()> {
+    let ref_name = format!("{}HEAD", remote_refs_prefix(name));
+    gix::validate::reference::name(ref_name.as_str().into())
+        .map_err(|e| anyhow!("Invalid remote name {}: {}", name, e))?;
+    Ok(())
+}
+
+fn remote_refs_prefix(name: &str) -> String {
+    format!("refs/remotes/{}/", name)
+}
+
+/// Rename the remote-tracking refs of remote `name` to be those of `new_name`, or delete them if none.
+fn move_remote_refs(path: &Path, name: &str, new_name: Option<&str>) -> Result<()> {
+    let mut repo = gix::discover(path)?;
+    {
+        // as with git, lack of a configured identity shouldn't prevent writing the reflog
+        use gix::config::tree::gitoxide::Committer;
+        let mut config = repo.config_snapshot_mut();
+        config.set_value(&Committer::NAME_FALLBACK, env!("CARGO_PKG_NAME"))?;
+        config.set_value(&Committer::EMAIL_FALLBACK, "gitmoto@localhost")?;
+    }
+
+    let prefix = remote_refs_prefix(name);
+    let new_prefix = new_name.map(remote_refs_prefix);
+    let renamed = |full_name: &BStr, new_prefix: &str| -> Result<FullName> {
+        Ok(full_name
+            .to_str_lossy()
+            .replacen(&prefix, new_prefix, 1)
+            .try_into()?)
+    };
+
+    let mut edits = Vec::new();
+    for reference in repo.references()?.prefixed(prefix.as_str())? {
+        let reference = reference.map_err(|e| anyhow!(e))?.detach();
+
+        if let Some(new_prefix) = new_prefix.as_ref() {
+            let new_target = match &reference.target {
+                Target::Symbolic(target) if target.as_bstr().starts_with(prefix.as_bytes()) => {
+                    Target::Symbolic(renamed(target.as_bstr(), new_prefix)?)
+                }
+                target => target.clone(),
+            };
+            edits.push(RefEdit {
+                change: Change::Update {
+                    log: LogChange {
+                        mode: RefLog::AndReference,
+                        force_create_reflog: false,
+                        message: format!("remote: renamed {} to {}", prefix, new_prefix).into(),
+                    },
+                    expected: PreviousValue::MustNotExist,
+                    new: new_target,
+                },
+                name: renamed(reference.name.as_bstr(), new_prefix)?,
+                deref: false,
+            });
+        }
+
+        edits.push(RefEdit {
+            change: Change::Delete {
+                expected: PreviousValue::MustExistAndMatch(reference.target),
+                log: RefLog::AndReference,
+            },
+            name: reference.name,
+            deref: false,
+        });
+    }
+
+    if !edits.is_empty() {
+        repo.edit_references(edits)?;
+    }
+
+    Ok(())
+}
+
+/// The local config file of a repo, for editing.
+struct LocalConfig {
+    path: PathBuf,
+    file: gix::config::File<'static>,
+}
+
+impl LocalConfig {
+    fn open(repo_path: &Path) -> Result<Self> {
+        let repo = gix::discover(repo_path)
+            .with_context(|| format!("Failed to open {}", repo_path.to_string_lossy()))?;
+        let path = repo.git_dir().join("config");
+        let file =
+            gix::config::File::from_path_no_includes(path.clone(), gix::config::Source::Local)
+                .with_context(|| format!("Failed to read {}", path.to_string_lossy()))?;
+
+        Ok(Self { path, file })
+    }
+
+    fn remote_names(&self) -> Vec<String> {
+        let mut names = self
+            .file
+            .sections_by_name("remote")
+            .into_iter()
+            .flatten()
+            .filter_map(|section| section.header().subsection_name().map(BStr::to_string))
+            .collect::<Vec<_>>();
+        names.sort();
+        names.dedup();
+        names
+    }
+
+    fn reparse(&mut self) -> Result<()> {
+        let meta = self.file.meta().clone();
+        self.file = gix::config::File::from_bytes_owned(
+            &mut self.file.to_bstring().into(),
+            meta,
+            Default::default(),
+        )?;
+        Ok(())
+    }
+
+    /// Fail unless remote `name` exists, or doesn't, as given by `exists`.
+    fn check_remote(&self, name: &str, exists: bool) -> Result<()> {
+        match (self.remote_names().iter().any(|n| n == name), exists) {
+            (false, true) => Err(anyhow!("No such remote {}", name)),
+            (true, false) => Err(anyhow!("Remote {} already exists", name)),
+            _ => Ok(()),
+        }
+    }
+
+    /// Names of branches whose upstream is on remote `name`.
+    fn branches_tracking(&self, name: &str) -> Vec<String> {
+        self.file
+            .sections_by_name("branch")
+            .into_iter()
+            .flatten()
+            .filter_map(|section| {
+                let branch = section.header().subsection_name()?;
+                (section.value("remote")?.as_ref() == name.as_bytes().as_bstr())
+                    .then(|| branch.to_string())
+            })
+            .collect()
+    }
+
+    /// Write the whole file via a lock file, as git does, so as not to race with git itself.
+    fn write(&self) -> Result<()> {
+        let mut lock_path = self.path.clone().into_os_string();
+        lock_path.push(".lock");
+        let lock_path = PathBuf::from(lock_path);
+
+        let mut lock_file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&lock_path)
+            .with_context(|| format!("Failed to lock {}", self.path.to_string_lossy()))?;
+        if let Err(e) = lock_file.write_all(&self.file.to_bstring()) {
+            let _ = remove_file(&lock_path);
+            return Err(e)
+                .with_context(|| format!("Failed to write {}", lock_path.to_string_lossy()));
+        }
+        rename(&lock_path, &self.path)
+            .with_context(|| format!("Failed to replace {}", self.path.to_string_lossy()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, process::Command};
+    use tempfile::TempDir;
+    use test_case::test_case;
+
+    use super::*;
+
+    const CONFIG: &str = r#"
+[remote "origin"]
+	url = git@github.com:acme/widget.git
+	fetch = +refs/heads/*:refs/remotes/origin/*
+[remote "upstream"]
+	url = https://github.com/widgets/widget.git
+	pushurl = git@github.com:widgets/widget.git
+	fetch = +refs/heads/*:refs/remotes/upstream/*
+[branch "main"]
+	remote = origin
+	merge = refs/heads/main
+"#;
+
+    fn git_command(dir: &Path, args: &[&str]) -> Command {
+        let mut command = Command::new("git");
+        command
+            .args(args)
+            .current_dir(dir)
+            .env("GIT_CONFIG_GLOBAL", "/dev/null")
+            .env("GIT_CONFIG_NOSYSTEM", "1")
+            .env("GIT_AUTHOR_NAME", "test")
+            .env("GIT_AUTHOR_EMAIL", "test@localhost")
+            .env("GIT_COMMITTER_NAME", "test")
+            .env("GIT_COMMITTER_EMAIL", "test@localhost");
+        command
+    }
+
+    /// Standard output of git, failing the test if git fails.
+    fn git(dir: &Path, args: &[&str]) -> String {
+        let output = git_command(dir, args).output().unwrap();
+        assert!(
+            output.status.success(),
+            "git {:?} failed: {}",
+            args,
+            String::from_utf8_lossy(&output.stderr)
+        );
+        String::from_utf8(output.stdout).unwrap()
+    }
+
+    /// A new repo with the remotes and branch of `CONFIG`.
+    fn repo() -> TempDir {
+        let dir = TempDir::new().unwrap();
+        git(dir.path(), &["init", "-q", "-b", "main"]);
+        let config_path = dir.path().join(".git/config");
+        let config = fs::read_to_string(&config_path).unwrap() + CONFIG;
+        fs::write(&config_path, config).unwrap();
+        dir
+    }
+
+    fn config_text(dir: &TempDir) -> String {
+        fs::read_to_string(dir.path().join(".git/config")).unwrap()
+    }
+
+    /// All values of the key as git itself reads them, so failing the test if the config is invalid.
+    fn config_values(dir: &TempDir, key: &str) -> Vec<String> {
+        let output = git_command(dir.path(), &["config", "--get-all", key])
+            .output()
+            .unwrap();
+        // exit code 1 is for the key not being found
+        assert!(
+            output.status.success() || output.status.code() == Some(1),
+            "git config failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        String::from_utf8(output.stdout)
+            .unwrap()
+            .lines()
+            .map(str::to_string)
+            .collect()
+    }
+
+    #[test]
+    fn test_rewrite_no_match() {
+        let dir = repo();
+        let before = config_text(&dir);
+        let rewrite = UrlRewrite::new("gitlab.com", "example.com");
+        assert_eq!(rewrite_remote_urls(dir.path(), &rewrite).unwrap(), 0);
+        assert_eq!(config_text(&dir), before);
+    }
+
+    #[test]
+    fn test_rewrite_all_remotes() {
+        let dir = repo();
+        let rewrite = UrlRewrite::new("github.com", "example.com");
+        assert_eq!(rewrite_remote_urls(dir.path(), &rewrite).unwrap(), 3);
+        assert_eq!(
+            config_values(&dir, "remote.origin.url"),
+            vec!["git@example.com:acme/widget.git"]
+        );
+        assert_eq!(
+            config_values(&dir, "remote.upstream.url"),
+            vec!["https://example.com/widgets/widget.git"]
+        );
+        assert_eq!(
+            config_values(&dir, "remote.upstream.pushurl"),
+            vec!["git@example.com:widgets/widget.git"]
+        );
+        // other keys are untouched
+        assert_eq!(
+            config_values(&dir, "remote.origin.fetch"),
+            vec!["+refs/heads/*:refs/remotes/origin/*"]
+        );
+        assert_eq!(
+            config_values(&dir, "branch.main.merge"),
+            vec!["refs/heads/main"]
+        );
+    }
+
+    #[test]
+    fn test_rewrite_push_urls() {
+        let dir = repo();
+        let rewrite = UrlRewrite::new("git@github.com:", "ssh://git@example.com/");
+        assert_eq!(rewrite_remote_urls(dir.path(), &rewrite).unwrap(), 2);
+        assert_eq!(
+            config_values(&dir, "remote.origin.url"),
+            vec!["ssh://git@example.com/acme/widget.git"]
+        );
+        assert_eq!(
+            config_values(&dir, "remote.upstream.url"),
+            vec!["https://github.com/widgets/widget.git"]
+        );
+        assert_eq!(
+            config_values(&dir, "remote.upstream.pushurl"),
+            vec!["ssh://git@example.com/widgets/widget.git"]
+        );
+    }
+
+    #[test]
+    fn test_rewrite_via_lock_file() {
+        let dir = repo();
+        let lock_path = dir.path().join(".git/config.lock");
+        let rewrite = UrlRewrite::new("github.com", "example.com");
+
+        // as when git itself is writing the config
+        fs::write(&lock_path, "").unwrap();
+        let before = config_text(&dir);
+        assert!(rewrite_remote_urls(dir.path(), &rewrite).is_err());
+        assert_eq!(config_text(&dir), before);
+
+        fs::remove_file(&lock_path).unwrap();
+        assert_eq!(rewrite_remote_urls(dir.path(), &rewrite).unwrap(), 3);
+        assert!(!lock_path.exists());
+        let remotes = git(dir.path(), &["remote", "-v"]);
+        assert!(remotes.contains("origin\tgit@example.com:acme/widget.git (fetch)"));
+        assert!(!remotes.contains("github.com"));
+    }
+
+    /// A repo with a commit and remote-tracking refs for both remotes, as if fetched.
+    fn fetched_repo() -> TempDir {
+        let dir = repo();
+        git(
+            dir.path(),
+            &["commit", "-q", "--allow-empty", "-m", "initial"],
+        );
+        for remote in ["origin", "upstream"] {
+            let main = format!("refs/remotes/{}/main", remote);
+            git(dir.path(), &["update-ref", &main, "HEAD"]);
+            git(
+                dir.path(),
+                &[
+                    "symbolic-ref",
+                    &format!("refs/remotes/{}/HEAD", remote),
+                    &main,
+                ],
+            );
+        }
+        dir
+    }
+
+    fn remote_refs(dir: &TempDir) -> Vec<String> {
+        git(
+            dir.path(),
+            &["for-each-ref", "--format=%(refname)", "refs/remotes/"],
+        )
+        .lines()
+        .map(str::to_string)
+        .collect()
+    }
+
+    #[test]
+    fn test_rename_remote() {
+        let dir = fetched_repo();
+        let edit = RemoteEdit::Rename {
+            name: "origin".to_string(),
+            new_name: "mirror".to_string(),
+        };
+        edit_remote(dir.path(), &edit).unwrap();
+
+        assert_eq!(
+            config_values(&dir, "remote.mirror.url"),
+            vec!["git@github.com:acme/widget.git"]
+        );
+        assert_eq!(
+            config_values(&dir, "remote.mirror.fetch"),
+            vec!["+refs/heads/*:refs/remotes/mirror/*"]
+        );
+        assert!(config_values(&dir, "remote.origin.url").is_empty());
+        assert_eq!(config_values(&dir, "branch.main.remote"), vec!["mirror"]);
+        assert_eq!(
+            remote_refs(&dir),
+            vec![
+                "refs/remotes/mirror/HEAD",
+                "refs/remotes/mirror/main",
+                "refs/remotes/upstream/HEAD",
+                "refs/remotes/upstream/main",
+            ]
+        );
+        assert_eq!(
+            git(dir.path(), &["symbolic-ref", "refs/remotes/mirror/HEAD"]).trim(),
+            "refs/remotes/mirror/main"
+        );
+    }
+
+    #[test]
+    fn test_rename_remote_to_existing() {
+        let dir = fetched_repo();
+        let before = config_text(&dir);
+        let edit = RemoteEdit::Rename {
+            name: "origin".to_string(),
+            new_name: "upstream".to_string(),
+        };
+        let e = edit_remote(dir.path(), &edit).unwrap_err();
+        assert_eq!(e.to_string(), "Remote upstream already exists");
+        assert_eq!(config_text(&dir), before);
+        assert_eq!(remote_refs(&dir).len(), 4);
+    }
+
+    #[test_case("" ; "empty")]
+    #[test_case("my mirror" ; "space")]
+    #[test_case("a..b" ; "double dot")]
+    #[test_case("mirror.lock" ; "lock suffix")]
+    fn test_rename_remote_invalid(new_name: &str) {
+        let dir = fetched_repo();
+        let before = config_text(&dir);
+        let edit = RemoteEdit::Rename {
+            name: "origin".to_string(),
+            new_name: new_name.to_string(),
+        };
+        let e = edit_remote(dir.path(), &edit).unwrap_err();
+        assert!(e
+            .to_string()
+            .starts_with(&format!("Invalid remote name {}: ", new_name)));
+        assert_eq!(config_text(&dir), before);
+        assert_eq!(remote_refs(&dir).len(), 4);
+    }
+
+    #[test]
+    fn test_add_remote_invalid() {
+        let dir = fetched_repo();
+        let before = config_text(&dir);
+        let edit = RemoteEdit::Add {
+            name: "fork~1".to_string(),
+            url: "git@github.com:me/widget.git".to_string(),
+        };
+        let e = edit_remote(dir.path(), &edit).unwrap_err();
+        assert!(e.to_string().starts_with("Invalid remote name fork~1: "));
+        assert_eq!(config_text(&dir), before);
+    }
+
+    #[test]
+    fn test_remove_remote() {
+        let dir = fetched_repo();
+        let edit = RemoteEdit::Remove {
+            name: "origin".to_string(),
+        };
+        edit_remote(dir.path(), &edit).unwrap();
+
+        assert!(config_values(&dir, "remote.origin.url").is_empty());
+        assert!(config_values(&dir, "remote.origin.fetch").is_empty());
+        assert!(config_values(&dir, "branch.main.remote").is_empty());
+        assert!(config_values(&dir, "branch.main.merge").is_empty());
+        assert_eq!(
+            config_values(&dir, "remote.upstream.url"),
+            vec!["https://github.com/widgets/widget.git"]
+        );
+        assert_eq!(
+            remote_refs(&dir),
+            vec!["refs/remotes/upstream/HEAD", "refs/remotes/upstream/main"]
+        );
+    }
+
+    #[test]
+    fn test_unset_push_url() {
+        let dir = repo();
+        let edit = RemoteEdit::SetPushUrl {
+            name: "upstream".to_string(),
+            url: None,
+        };
+        edit_remote(dir.path(), &edit).unwrap();
+
+        assert!(config_values(&dir, "remote.upstream.pushurl").is_empty());
+        assert_eq!(
+            config_values(&dir, "remote.upstream.url"),
+            vec!["https://github.com/widgets/widget.git"]
+        );
+    }
+
+    /// Commit on the current branch, at `time` seconds since the epoch, returning its id.
+    fn commit_at(dir: &TempDir, message: &str, time: i64) -> String {
+        let date = format!("@{} +0000", time);
+        let output = git_command(
+            dir.path(),
+            &["commit", "-q", "--allow-empty", "-m", message],
+        )
+        .env("GIT_AUTHOR_DATE", &date)
+        .env("GIT_COMMITTER_DATE", &date)
+        .output()
+        .unwrap();
+        assert!(output.status.success());
+        git(dir.path(), &["rev-parse", "HEAD"]).trim().to_string()
+    }
+
+    /// Diverged history, with a merge of a side branch on main:
+    ///
+    /// ```text
+    ///       x -----.
+    ///      /        \
+    /// r - a - b ---- m - c   main
+    ///      \
+    ///       u1 - u2          origin/main
+    /// ```
+    ///
+    /// with all commits at the same time if `same_time`, otherwise in the order made.
+    #[test_case(false; "in order")]
+    #[test_case(true; "same time")]
+    fn test_ahead_behind(same_time: bool) {
+        let dir = repo();
+        let mut n_commits = 0;
+        let mut commit = |message: &str| {
+            n_commits += 1;
+            let time = if same_time { 1 } else { n_commits };
+            commit_at(&dir, message, 1_700_000_000 + time)
+        };
+        let git = |args: &[&str]| git(dir.path(), args);
+
+        commit("r");
+        let a = commit("a");
+        commit("b");
+        git(&["checkout", "-q", "-b", "side", &a]);
+        commit("x");
+        git(&["checkout", "-q", "main"]);
+        git(&["merge", "-q", "--no-ff", "--no-edit", "side"]);
+        commit("c");
+        git(&["checkout", "-q", "-b", "upstream", &a]);
+        commit("u1");
+        let u2 = commit("u2");
+        git(&["checkout", "-q", "main"]);
+        git(&["update-ref", "refs/remotes/origin/main", &u2]);
+
+        let repo = gix::open(dir.path()).unwrap();
+        assert_eq!(ahead_behind(&repo).unwrap(), Some((4, 2)));
+        assert_eq!(
+            git(&["rev-list", "--left-right", "--count", "main...origin/main"]),
+            "4\t2\n"
+        );
+
+        git(&["update-ref", "refs/remotes/origin/main", "main"]);
+        assert_eq!(ahead_behind(&repo).unwrap(), Some((0, 0)));
+        git(&["update-ref", "refs/remotes/origin/main", &a]);
+        assert_eq!(ahead_behind(&repo).unwrap(), Some((4, 0)));
+    }
+}
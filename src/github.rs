@@ -1,139 +1,520 @@
-use anyhow::Context;
 use async_stream::stream;
-use futures::Stream;
+use chrono::{DateTime, TimeDelta, Utc};
+use futures::{Stream, StreamExt};
+use http::{
+    header::{ETAG, IF_NONE_MATCH},
+    HeaderMap, HeaderValue, StatusCode, Uri,
+};
 use leaky_bucket::RateLimiter;
-use octocrab::models::Repository;
-use std::{collections::HashMap, default::Default, io::Read, time::Duration};
-use subprocess::Exec;
-use tracing::{debug, info};
+use octocrab::{map_github_error, models::Repository, FromResponse, Octocrab, Page};
+use serde::{de::DeserializeOwned, Deserialize};
+use std::{collections::HashMap, env, fmt::Display, sync::Mutex, time::Duration};
+use subprocess::{Exec, NullFile, Redirection};
+use tracing::{debug, info, warn};
+
+use crate::{config::GitHubConfig, model::GitHubRepo};
+pub mod rate_limit;
+use rate_limit::{backoff, until, Budget, Budgets};
+
+const GITHUB_HOST: &str = "github.com";
+const GITHUB_API_HOST: &str = "api.github.com";
+
+/// The most GitHub allows, so as few requests as possible count against the rate limit.
+const PER_PAGE: u8 = 100;
+
+/// Attempts at a request refused for being rate limited, before giving up.
+const MAX_ATTEMPTS: u32 = 5;
 
 pub struct Connection {
-    auth_token: Option<String>,
-    rate_limiters: HashMap<bool, Vec<RateLimiter>>, // key is whether we have an auth token
+    octocrab: Octocrab,
+    /// path of the API below the host, as for GitHub Enterprise, without trailing slash
+    base_path: String,
+    authenticated: bool,
+    /// whose budget this connection spends, by host and whether authenticated
+    budget_key: String,
+    /// as reported in the most recent response
+    budget: Mutex<Option<Budget>>,
+    /// no requests before then, having been told to back off
+    not_before: Mutex<Option<DateTime<Utc>>>,
+    /// secondary limit on REST requests, which responses don't report
+    per_minute: RateLimiter,
+    /// pages fetched earlier, by route, so unchanged ones cost no quota
+    etagged: Mutex<HashMap<String, EtaggedPage>>,
+}
+
+struct EtaggedPage {
+    etag: HeaderValue,
+    items: Vec<serde_json::Value>,
+    next: Option<Uri>,
 }
 
 impl Connection {
-    pub fn new() -> Self {
-        let mut this = Self {
-            auth_token: None,
+    pub fn new(config: &GitHubConfig) -> Result<Self, Error> {
+        let mut builder = Octocrab::builder();
+        let mut base_path = String::new();
+        if let Some(base_url) = config.base_url.as_ref() {
+            builder = builder
+                .base_uri(base_url.as_str())
+                .map_err(|e| Error::BaseUrl(base_url.clone(), Box::new(e)))?;
+            base_path = url_path(base_url).trim_end_matches('/').to_string();
+        }
+
+        let host = web_host(config.base_url.as_deref());
+        let token = auth_token(config, host);
+        let authenticated = token.is_some();
+        if let Some(token) = token {
+            builder = builder.personal_token(token);
+        }
+        let octocrab = builder.build().map_err(|e| Error::Client(Box::new(e)))?;
+
+        Ok(Self {
+            octocrab,
+            base_path,
+            authenticated,
+            budget_key: if authenticated {
+                host.to_string()
+            } else {
+                format!("{} unauthenticated", host)
+            },
+            budget: Mutex::new(None),
+            not_before: Mutex::new(None),
             // https://docs.github.com/en/rest/using-the-rest-api/rate-limits-for-the-rest-api
-            rate_limiters: HashMap::from([
-                (
-                    false, // unauthenticated
-                    vec![
-                        // unauthenticated primary
-                        RateLimiter::builder()
-                            .fair(false)
-                            .max(60)
-                            .initial(60)
-                            .interval(Duration::from_secs(60 * 60)) // 1 hour
-                            .refill(60)
-                            .build(),
-                        // unauthenticated secondary REST GET, HEAD, OPTIONS
-                        RateLimiter::builder()
-                            .fair(false)
-                            .max(900)
-                            .initial(900)
-                            .interval(Duration::from_secs(60)) // 1 min
-                            .refill(900)
-                            .build(),
-                    ],
-                ),
-                (
-                    true, // authenticated
-                    vec![
-                        // authenticated primary
-                        RateLimiter::builder()
-                            .fair(false)
-                            .max(5000)
-                            .initial(5000)
-                            .interval(Duration::from_secs(60 * 60)) // 1 hour
-                            .refill(5000)
-                            .build(),
-                        // authenticated secondary REST GET, HEAD, OPTIONS
-                        RateLimiter::builder()
-                            .fair(false)
-                            .max(900)
-                            .initial(900)
-                            .interval(Duration::from_secs(60)) // 1 min
-                            .refill(900)
-                            .build(),
-                    ],
-                ),
-            ]),
-        };
-
-        this.try_auth();
-
-        this
-    }
-
-    fn try_auth(&mut self) {
-        match Exec::cmd("gh")
-            .args(&["auth", "token"])
-            .stream_stdout()
-            .context("gh auth token")
-        {
-            Ok(mut stream) => {
-                let mut token = String::new();
-                stream.read_to_string(&mut token).unwrap();
-                debug!("acquired GitHub token");
-                self.auth_token = Some(token);
-            }
-            Err(e) => {
-                debug!("failed to acquired GitHub token: {}", e);
+            per_minute: RateLimiter::builder()
+                .fair(false)
+                .max(900)
+                .initial(900)
+                .interval(Duration::from_secs(60)) // 1 min
+                .refill(900)
+                .build(),
+            etagged: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Start from the budget left by an earlier run, unless it has been reset since.
+    pub fn restore_budget(&self, budgets: &Budgets) {
+        if let Some(budget) = budgets.get(&self.budget_key) {
+            if budget.reset > Utc::now() {
+                debug!("restored GitHub rate limit budget {:?}", budget);
+                *self.budget.lock().unwrap() = Some(*budget);
             }
         }
     }
 
+    pub fn save_budget(&self, budgets: &mut Budgets) {
+        if let Some(budget) = *self.budget.lock().unwrap() {
+            budgets.insert(self.budget_key.clone(), budget);
+        }
+    }
+
     async fn rate_limit_one(&self) {
-        let authorized = self.auth_token.is_some();
-        for (i, rate_limiter) in self.rate_limiters[&authorized].iter().enumerate() {
-            if !rate_limiter.try_acquire(1) {
-                info!(
-                    "rate limited {} auth by {} {:?}, please wait",
-                    if authorized { "with" } else { "without" },
-                    i,
-                    &rate_limiter
+        let now = Utc::now();
+        let spent = self
+            .budget
+            .lock()
+            .unwrap()
+            .and_then(|budget| budget.wait(now));
+        let backing_off = self
+            .not_before
+            .lock()
+            .unwrap()
+            .and_then(|not_before| until(now, not_before));
+        if let Some(wait) = spent.max(backing_off) {
+            info!(
+                "rate limited {} auth, waiting {}s",
+                if self.authenticated {
+                    "with"
+                } else {
+                    "without"
+                },
+                wait.as_secs()
+            );
+            if !self.authenticated {
+                info!("login to GitHub for higher rate");
+            }
+            tokio::time::sleep(wait).await;
+        }
+
+        if !self.per_minute.try_acquire(1) {
+            info!("rate limited by {:?}, please wait", &self.per_minute);
+            self.per_minute.acquire_one().await
+        }
+    }
+
+    /// One page of a listing and the link to the next, if any, reusing the earlier response
+    /// if GitHub says it is unchanged, and backing off if rate limited.
+    async fn page<T>(&self, route: &str) -> Result<(Vec<T>, Option<Uri>), Error>
+    where
+        T: DeserializeOwned,
+    {
+        let mut attempt = 0;
+        loop {
+            self.rate_limit_one().await;
+
+            let mut headers = HeaderMap::new();
+            if let Some(etagged) = self.etagged.lock().unwrap().get(route) {
+                headers.insert(IF_NONE_MATCH, etagged.etag.clone());
+            }
+            let response = self
+                .octocrab
+                ._get_with_headers(route, Some(headers))
+                .await?;
+            if let Some(budget) = Budget::from_headers(response.headers()) {
+                *self.budget.lock().unwrap() = Some(budget);
+            }
+
+            let status = response.status();
+            if status == StatusCode::NOT_MODIFIED {
+                if let Some(etagged) = self.etagged.lock().unwrap().get(route) {
+                    debug!("{} unchanged", route);
+                    return Ok((items(etagged.items.clone())?, etagged.next.clone()));
+                }
+            } else if status.is_success() {
+                let etag = response.headers().get(ETAG).cloned();
+                let page = Page::<serde_json::Value>::from_response(response).await?;
+                if let Some(etag) = etag {
+                    self.etagged.lock().unwrap().insert(
+                        route.to_string(),
+                        EtaggedPage {
+                            etag,
+                            items: page.items.clone(),
+                            next: page.next.clone(),
+                        },
+                    );
+                }
+                return Ok((items(page.items)?, page.next));
+            }
+
+            let headers = response.headers().clone();
+            let error = match map_github_error(response).await {
+                Err(e) => Error::from(e),
+                Ok(_) => Error::Api {
+                    status: status.as_u16(),
+                    message: status.to_string(),
+                },
+            };
+            let message = match &error {
+                Error::Api { message, .. } => message.as_str(),
+                _ => "",
+            };
+            match backoff(status, &headers, message, attempt, Utc::now()) {
+                Some(wait) if attempt + 1 < MAX_ATTEMPTS => {
+                    warn!("{} for {}, backing off {}s", &error, route, wait.as_secs());
+                    *self.not_before.lock().unwrap() = TimeDelta::from_std(wait)
+                        .ok()
+                        .and_then(|wait| Utc::now().checked_add_signed(wait));
+                    attempt += 1;
+                }
+                _ => return Err(error),
+            }
+        }
+    }
+
+    /// Repos of the authenticated user, both their own and those of the orgs they belong to,
+    /// or without a token, the public repos of `user` and their public orgs.
+    pub fn repos<'a>(
+        &'a self,
+        user: Option<&'a str>,
+    ) -> impl Stream<Item = Result<GitHubRepo, Error>> + 'a {
+        stream! {
+            if self.authenticated {
+                let route = format!(
+                    "/user/repos?affiliation=owner,organization_member&per_page={}",
+                    PER_PAGE
                 );
-                if !authorized {
-                    info!("login to GitHub for higher rate");
+                for await repo in self.all_pages(route) {
+                    yield repo.map(github_repo);
+                }
+            } else if let Some(user) = user {
+                info!("no GitHub token, so listing only public repos of {}", user);
+                let route = format!("/users/{}/repos?per_page={}", user, PER_PAGE);
+                for await repo in self.all_pages(route) {
+                    yield repo.map(github_repo);
+                }
+
+                let route = format!("/users/{}/orgs?per_page={}", user, PER_PAGE);
+                let orgs = self.all_pages::<Org>(route).collect::<Vec<_>>().await;
+                for org in orgs {
+                    match org {
+                        Ok(org) => {
+                            let route = format!("/orgs/{}/repos?per_page={}", org.login, PER_PAGE);
+                            for await repo in self.all_pages(route) {
+                                yield repo.map(github_repo);
+                            }
+                        }
+                        Err(e) => yield Err(e),
+                    }
                 }
-                rate_limiter.acquire_one().await
+            } else {
+                yield Err(Error::NoTokenOrUser);
             }
         }
     }
 
-    pub fn git_dirs<'a, 'b, S>(&'a self, user: S) -> impl Stream<Item = String> + 'b
+    /// Items of every page of a listing, starting from the route of the first.
+    fn all_pages<'a, T>(&'a self, first: String) -> impl Stream<Item = Result<T, Error>> + 'a
     where
-        S: Into<String>,
-        'a: 'b,
+        T: DeserializeOwned + 'a,
     {
-        let user: String = user.into();
         stream! {
-            let octocrab = octocrab::instance();
-            let user = octocrab.users(user);
-            let repos = user.repos();
+            let mut route = Some(first);
+            while let Some(current) = route.take() {
+                match self.page::<T>(&current).await {
+                    Ok((items, next)) => {
+                        for item in items {
+                            yield Ok(item);
+                        }
+                        // octocrab prefixes the base path even to absolute URLs, so strip it here
+                        route = next.map(|next| relative_route(&self.base_path, next.path(), next.query()));
+                    }
+                    Err(e) => yield Err(e),
+                }
+            }
+        }
+    }
+}
 
-            self.rate_limit_one().await;
-            let mut page = Some(repos.send().await.unwrap());
+#[derive(Debug)]
+pub enum Error {
+    /// the configured base URL, and why it was rejected
+    BaseUrl(String, Box<octocrab::Error>),
+    Client(Box<octocrab::Error>),
+    /// an error response from the API, such as for bad credentials or exceeding the rate limit
+    Api {
+        status: u16,
+        message: String,
+    },
+    /// no response, or one which couldn't be understood
+    Request(Box<octocrab::Error>),
+    /// a listing of something other than expected
+    Json(serde_json::Error),
+    NoTokenOrUser,
+}
 
-            while let Some(mut current_page) = page {
-            for repo in current_page.take_items() {
-                yield repo.name;
-            }
+impl From<octocrab::Error> for Error {
+    fn from(e: octocrab::Error) -> Self {
+        match e {
+            octocrab::Error::GitHub { source, .. } => Error::Api {
+                status: source.status_code.as_u16(),
+                message: source.message,
+            },
+            e => Error::Request(Box::new(e)),
+        }
+    }
+}
 
-            if current_page.next.is_some() {
-                self.rate_limit_one().await;
-            }
-                page = octocrab.get_page::<Repository>(&current_page.next).await.unwrap();
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use Error::*;
+        match self {
+            BaseUrl(url, e) => write!(f, "bad GitHub base URL {}: {}", url, e),
+            Client(e) => write!(f, "failed to create GitHub client: {}", e),
+            Api { status, message } => write!(f, "GitHub API error {}: {}", status, message),
+            Request(e) => write!(f, "GitHub request failed: {}", e),
+            Json(e) => write!(f, "unexpected GitHub response: {}", e),
+            NoTokenOrUser => {
+                f.write_str("no GitHub token, nor github.user whose public repos to list")
             }
         }
     }
 }
 
-impl Default for Connection {
-    fn default() -> Self {
-        Self::new()
+impl std::error::Error for Error {}
+
+fn items<T>(items: Vec<serde_json::Value>) -> Result<Vec<T>, Error>
+where
+    T: DeserializeOwned,
+{
+    serde_json::from_value(serde_json::Value::Array(items)).map_err(Error::Json)
+}
+
+/// Path part of the URL, empty if none.
+fn url_path(url: &str) -> &str {
+    let without_scheme = url.split_once("://").map_or(url, |(_, rest)| rest);
+    without_scheme
+        .find('/')
+        .map_or("", |i| &without_scheme[i..])
+}
+
+/// Host of the web UI for the API at the base URL, as known to `gh` and git credential helpers.
+fn web_host(base_url: Option<&str>) -> &str {
+    let Some(base_url) = base_url else {
+        return GITHUB_HOST;
+    };
+    let without_scheme = base_url
+        .split_once("://")
+        .map_or(base_url, |(_, rest)| rest);
+    match without_scheme.split('/').next().unwrap_or(without_scheme) {
+        GITHUB_API_HOST => GITHUB_HOST,
+        host => host,
+    }
+}
+
+/// The route of a next page link relative to the base path, which octocrab will prefix.
+fn relative_route(base_path: &str, path: &str, query: Option<&str>) -> String {
+    let path = path
+        .strip_prefix(base_path)
+        .filter(|path| path.starts_with('/'))
+        .unwrap_or(path);
+    match query {
+        Some(query) => format!("{}?{}", path, query),
+        None => path.to_string(),
+    }
+}
+
+/// Just enough of an org in a listing to list its repos.
+#[derive(Deserialize)]
+struct Org {
+    login: String,
+}
+
+fn github_repo(repo: Repository) -> GitHubRepo {
+    let owner = repo
+        .owner
+        .map(|owner| owner.login)
+        .or_else(|| {
+            repo.full_name
+                .as_deref()
+                .and_then(|full_name| full_name.split_once('/'))
+                .map(|(owner, _)| owner.to_string())
+        })
+        .unwrap_or_default();
+    GitHubRepo {
+        full_name: repo
+            .full_name
+            .unwrap_or_else(|| format!("{}/{}", &owner, &repo.name)),
+        owner,
+        name: repo.name,
+        clone_url: repo.clone_url.map(|url| url.to_string()),
+        ssh_url: repo.ssh_url,
+        fork: repo.fork.unwrap_or(false),
+        archived: repo.archived.unwrap_or(false),
+        private: repo.private.unwrap_or(false),
+        default_branch: repo.default_branch,
+        pushed_at: repo.pushed_at,
+    }
+}
+
+/// The first token found in `GITHUB_TOKEN`, the config, `gh`, or git's credential helpers.
+fn auth_token(config: &GitHubConfig, host: &str) -> Option<String> {
+    let from = |source: &str, token: Option<String>| {
+        let token = trimmed_token(&token?)?;
+        debug!("acquired GitHub token from {}", source);
+        Some(token)
+    };
+    from("GITHUB_TOKEN", env::var("GITHUB_TOKEN").ok())
+        .or_else(|| from("config", config.token.clone()))
+        .or_else(|| from("gh auth token", gh_auth_token(host)))
+        .or_else(|| from("git credential fill", git_credential_fill(host)))
+}
+
+/// The token without surrounding whitespace, such as the newline output by `gh`, if any remains.
+fn trimmed_token(token: &str) -> Option<String> {
+    let token = token.trim();
+    (!token.is_empty()).then(|| token.to_string())
+}
+
+fn gh_auth_token(host: &str) -> Option<String> {
+    capture_stdout(Exec::cmd("gh").args(&["auth", "token", "--hostname", host]))
+}
+
+/// The password from whatever credential helper git is configured with, without prompting.
+fn git_credential_fill(host: &str) -> Option<String> {
+    let output = capture_stdout(
+        Exec::cmd("git")
+            .args(&["credential", "fill"])
+            .env("GIT_TERMINAL_PROMPT", "0")
+            .env_remove("GIT_ASKPASS")
+            .env_remove("SSH_ASKPASS")
+            .stdin(format!("protocol=https\nhost={}\n\n", host).as_str()),
+    )?;
+    credential_password(&output).map(str::to_string)
+}
+
+fn credential_password(output: &str) -> Option<&str> {
+    output
+        .lines()
+        .find_map(|line| line.strip_prefix("password="))
+}
+
+/// Standard output of the command, if it succeeds.
+fn capture_stdout(exec: Exec) -> Option<String> {
+    let cmdline = exec.to_cmdline_lossy();
+    match exec.stdout(Redirection::Pipe).stderr(NullFile).capture() {
+        Ok(capture) if capture.success() => Some(capture.stdout_str()),
+        Ok(capture) => {
+            debug!("{} failed: {:?}", cmdline, capture.exit_status);
+            None
+        }
+        Err(e) => {
+            debug!("{} failed: {}", cmdline, e);
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+
+    #[test_case("gho_abc\n", Some("gho_abc"); "newline")]
+    #[test_case("  gho_abc \r\n", Some("gho_abc"); "whitespace")]
+    #[test_case("\n", None; "empty")]
+    fn test_trimmed_token(token: &str, expected: Option<&str>) {
+        assert_eq!(trimmed_token(token).as_deref(), expected);
+    }
+
+    #[test]
+    fn test_github_repo() {
+        let repo = serde_json::from_value::<Repository>(serde_json::json!({
+            "id": 1,
+            "name": "widget",
+            "full_name": "acme/widget",
+            "url": "https://api.github.com/repos/acme/widget",
+            "clone_url": "https://github.com/acme/widget.git",
+            "ssh_url": "git@github.com:acme/widget.git",
+            "private": true,
+            "archived": true,
+            "default_branch": "main",
+            "pushed_at": "2024-05-06T07:08:09Z",
+        }))
+        .unwrap();
+        let repo = github_repo(repo);
+        assert_eq!(repo.owner, "acme");
+        assert_eq!(repo.full_name, "acme/widget");
+        assert_eq!(
+            repo.clone_url.as_deref(),
+            Some("https://github.com/acme/widget.git")
+        );
+        assert_eq!(
+            (repo.fork, repo.archived, repo.private),
+            (false, true, true)
+        );
+        assert_eq!(
+            repo.pushed_at.map(|t| t.to_rfc3339()).as_deref(),
+            Some("2024-05-06T07:08:09+00:00")
+        );
+    }
+
+    #[test_case(None, "github.com"; "default")]
+    #[test_case(Some("https://api.github.com"), "github.com"; "explicit default")]
+    #[test_case(Some("https://github.example.com/api/v3"), "github.example.com"; "enterprise")]
+    #[test_case(Some("http://127.0.0.1:8080"), "127.0.0.1:8080"; "port")]
+    fn test_web_host(base_url: Option<&str>, expected: &str) {
+        assert_eq!(web_host(base_url), expected);
+    }
+
+    #[test_case("/api/v3", "/api/v3/user/repos", Some("page=2"), "/user/repos?page=2"; "enterprise")]
+    #[test_case("", "/user/repos", Some("page=2"), "/user/repos?page=2"; "default")]
+    #[test_case("/api", "/apix/repos", None, "/apix/repos"; "not a path prefix")]
+    fn test_relative_route(base_path: &str, path: &str, query: Option<&str>, expected: &str) {
+        assert_eq!(relative_route(base_path, path, query), expected);
+    }
+
+    #[test_case("protocol=https\nhost=github.com\nusername=me\npassword=gho_abc\n", Some("gho_abc"); "password")]
+    #[test_case("protocol=https\nhost=github.com\n", None; "none")]
+    fn test_credential_password(output: &str, expected: Option<&str>) {
+        assert_eq!(credential_password(output), expected);
     }
 }
@@ -0,0 +1,141 @@
+use chrono::{DateTime, Utc};
+use http::{HeaderMap, StatusCode};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, str::FromStr, time::Duration};
+
+/// Wait at least this long after hitting a secondary rate limit which doesn't say how long,
+/// as GitHub advises, doubling for each further attempt.
+const MIN_SECONDARY_BACKOFF: Duration = Duration::from_secs(60);
+
+/// Budgets by host, and whether authenticated, as persisted between runs.
+pub type Budgets = BTreeMap<String, Budget>;
+
+/// What's left of the primary rate limit, as last reported by GitHub.
+#[derive(Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Debug)]
+pub struct Budget {
+    pub remaining: u32,
+    pub reset: DateTime<Utc>,
+}
+
+impl Budget {
+    /// From the `x-ratelimit-remaining` and `x-ratelimit-reset` headers of a response.
+    pub fn from_headers(headers: &HeaderMap) -> Option<Self> {
+        let remaining = header(headers, "x-ratelimit-remaining")?;
+        let reset = DateTime::from_timestamp(header(headers, "x-ratelimit-reset")?, 0)?;
+        Some(Self { remaining, reset })
+    }
+
+    /// How long until the next request may be made, if the budget is spent.
+    pub fn wait(&self, now: DateTime<Utc>) -> Option<Duration> {
+        if self.remaining == 0 {
+            until(now, self.reset)
+        } else {
+            None
+        }
+    }
+}
+
+/// How long to back off before retrying a request GitHub refused for being rate limited.
+///
+/// `attempt` is the number of times the request was already retried.
+pub fn backoff(
+    status: StatusCode,
+    headers: &HeaderMap,
+    message: &str,
+    attempt: u32,
+    now: DateTime<Utc>,
+) -> Option<Duration> {
+    if status != StatusCode::FORBIDDEN && status != StatusCode::TOO_MANY_REQUESTS {
+        return None;
+    }
+    if let Some(seconds) = header(headers, "retry-after") {
+        return Some(Duration::from_secs(seconds));
+    }
+    if let Some(wait) = Budget::from_headers(headers).and_then(|budget| budget.wait(now)) {
+        return Some(wait);
+    }
+    // any other 403 is for lack of permission, so not worth retrying
+    if status == StatusCode::TOO_MANY_REQUESTS || message.to_lowercase().contains("rate limit") {
+        Some(MIN_SECONDARY_BACKOFF * 2u32.saturating_pow(attempt))
+    } else {
+        None
+    }
+}
+
+/// How long from now until then, if then is in the future.
+pub fn until(now: DateTime<Utc>, then: DateTime<Utc>) -> Option<Duration> {
+    (then - now)
+        .to_std()
+        .ok()
+        .filter(|duration| !duration.is_zero())
+}
+
+fn header<T>(headers: &HeaderMap, name: &str) -> Option<T>
+where
+    T: FromStr,
+{
+    headers.get(name)?.to_str().ok()?.trim().parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+
+    const NOW: i64 = 1_700_000_000;
+
+    fn headers(pairs: &[(&'static str, &str)]) -> HeaderMap {
+        pairs
+            .iter()
+            .map(|(name, value)| (http::HeaderName::from_static(name), value.parse().unwrap()))
+            .collect()
+    }
+
+    fn now() -> DateTime<Utc> {
+        DateTime::from_timestamp(NOW, 0).unwrap()
+    }
+
+    #[test]
+    fn test_budget_from_headers() {
+        let reset = (NOW + 600).to_string();
+        let budget = Budget::from_headers(&headers(&[
+            ("x-ratelimit-remaining", "0"),
+            ("x-ratelimit-reset", &reset),
+        ]))
+        .unwrap();
+        assert_eq!(budget.remaining, 0);
+        assert_eq!(budget.wait(now()), Some(Duration::from_secs(600)));
+
+        let spare = Budget {
+            remaining: 1,
+            ..budget
+        };
+        assert_eq!(spare.wait(now()), None);
+    }
+
+    #[test_case(403, &[("retry-after", "30")], "", 0, Some(30); "retry after")]
+    #[test_case(403, &[("x-ratelimit-remaining", "0"), ("x-ratelimit-reset", "1700000120")], "", 0, Some(120); "primary exhausted")]
+    #[test_case(403, &[], "You have exceeded a secondary rate limit", 0, Some(60); "secondary")]
+    #[test_case(429, &[], "", 2, Some(240); "secondary again")]
+    #[test_case(403, &[], "Resource not accessible by integration", 0, None; "forbidden")]
+    #[test_case(404, &[("retry-after", "30")], "", 0, None; "not found")]
+    fn test_backoff(
+        status: u16,
+        pairs: &[(&'static str, &str)],
+        message: &str,
+        attempt: u32,
+        expected: Option<u64>,
+    ) {
+        assert_eq!(
+            backoff(
+                StatusCode::from_u16(status).unwrap(),
+                &headers(pairs),
+                message,
+                attempt,
+                now()
+            ),
+            expected.map(Duration::from_secs)
+        );
+    }
+}
@@ -0,0 +1,13 @@
+pub mod cache;
+pub mod cli; // non-interactive subcommands
+pub mod config;
+pub mod git; // local repo queries
+pub mod github; // GitHub API
+pub mod model;
+pub mod presenter;
+pub mod remote_url; // parsing of git remote URLs
+pub mod service;
+pub mod ssh; // ssh remote traversal
+pub mod template;
+pub mod tui;
+pub mod util;
@@ -1,20 +1,24 @@
 use anyhow::Result;
-use config::read_config;
+use clap::Parser;
 use ratatui::{backend::CrosstermBackend, Terminal};
 use std::{fs::OpenOptions, io, process::exit};
 use tokio::select;
-use tracing::trace;
+use tracing::{trace, warn};
 use tracing_subscriber::EnvFilter;
 
-use crate::{
-    model::UpdateModel,
-    presenter::Presenter,
-    service::{filesystem, terminal},
+use gitmoto::{
+    cache, cli,
+    cli::Cli,
+    config::read_config,
+    presenter::{Action, Presenter},
+    service::{exec, filesystem, github as github_service, ssh as ssh_service, terminal},
     tui::Tui,
 };
 
 #[tokio::main(flavor = "current_thread")]
 async fn main() -> Result<()> {
+    let cli = Cli::parse();
+
     let logfile = OpenOptions::new()
         .append(true)
         .create(true)
@@ -32,7 +36,18 @@ async fn main() -> Result<()> {
     trace!("");
 
     let config = read_config().expect("Failed to read config");
-    let mut presenter = Presenter::new(&config);
+
+    if let Some(command) = cli.command {
+        return cli::run(command, &config).await;
+    }
+
+    let model = cache::read_model()
+        .unwrap_or_else(|e| {
+            warn!("ignoring model cache: {}", e);
+            None
+        })
+        .unwrap_or_default();
+    let mut presenter = Presenter::new(&config, model);
 
     let backend = CrosstermBackend::new(io::stdout());
     let terminal = Terminal::new(backend)?;
@@ -42,6 +57,12 @@ async fn main() -> Result<()> {
     let mut terminal_service = terminal::Service::default();
     let mut filesystem_service = filesystem::Service::new(&config.filesystem);
     let filesystem_requester = filesystem_service.requester();
+    let mut exec_service = exec::Service::new(&config.exec);
+    let exec_requester = exec_service.requester();
+    let mut ssh_service = ssh_service::Service::new(&config.ssh);
+    let ssh_requester = ssh_service.requester();
+    let mut github_service = github_service::Service::new(&config.github);
+    let github_requester = github_service.requester();
 
     let roots = &config.filesystem.scanner.roots;
     if roots.is_empty() {
@@ -49,11 +70,11 @@ async fn main() -> Result<()> {
         exit(1);
     }
 
-    // initial filesystem scan, TODO read from cache
+    // background rescan to reconcile with what was read from the cache
     filesystem_requester
         .send(filesystem::Request::Scan)
-        .await
         .unwrap();
+    ssh_requester.send(ssh_service::Request::Scan).unwrap();
 
     // Start the main loop.
     let mut running = true;
@@ -64,15 +85,37 @@ async fn main() -> Result<()> {
         select! {
             ev = terminal_service.recv_event()  => {
                 if let Some(ev) = ev {
-                    let quit = terminal_service.handle(ev, |key| presenter.handle_key(key)).await;
-                    if quit {
-                        running = false;
+                    match terminal_service.handle(ev, |key| presenter.handle_key(key)).await {
+                        Some(Action::Quit) => running = false,
+                        Some(Action::Rescan) => {
+                            filesystem_requester.send(filesystem::Request::Scan).unwrap();
+                            ssh_requester.send(ssh_service::Request::Scan).unwrap();
+                        }
+                        Some(Action::Filesystem(request)) => filesystem_requester.send(request).unwrap(),
+                        Some(Action::Exec(request)) => exec_requester.send(request).unwrap(),
+                        Some(Action::GitHub(request)) => github_requester.send(request).unwrap(),
+                        None => {}
                     }
                 }
             },
-            key = filesystem_service.recv_event() => {
-                if let Some(key) = key {
-                    filesystem_service.handle(key, |repo| presenter.add_local_repo(repo)).await;
+            ev = filesystem_service.recv_event() => {
+                if let Some(ev) = ev {
+                    filesystem_service.handle(ev, &mut presenter).await;
+                }
+            }
+            ev = exec_service.recv_event() => {
+                if let Some(ev) = ev {
+                    exec_service.handle(ev, &mut presenter).await;
+                }
+            }
+            ev = ssh_service.recv_event() => {
+                if let Some(ev) = ev {
+                    ssh_service.handle(ev, &mut presenter).await;
+                }
+            }
+            ev = github_service.recv_event() => {
+                if let Some(ev) = ev {
+                    github_service.handle(ev, &mut presenter).await;
                 }
             }
         }
@@ -83,13 +126,3 @@ async fn main() -> Result<()> {
 
     Ok(())
 }
-
-pub mod config;
-pub mod github; // GitHub API
-pub mod model;
-pub mod presenter;
-pub mod service;
-pub mod ssh; // ssh remote traversal
-pub mod template;
-pub mod tui;
-pub mod util;
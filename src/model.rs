@@ -1,55 +1,830 @@
-use std::{collections::BTreeMap, default::Default, fmt::Display, path::PathBuf};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    default::Default,
+    fmt::{self, Display},
+    path::{Path, PathBuf},
+};
 
-#[derive(Default, Debug)]
+use crate::remote_url::{RemoteUrl, RepoIdentity};
+
+#[derive(Default, Serialize, Deserialize, Debug)]
 pub struct Model {
-    pub repos: BTreeMap<PathBuf, LocalRepo>,
+    /// serialized as a list, since each repo knows its own id
+    #[serde(with = "repos_as_list")]
+    pub repos: BTreeMap<RepoId, LocalRepo>,
+    /// repos seen so far in each scan in progress, by host
+    #[serde(skip)]
+    scanned: BTreeMap<Option<String>, BTreeSet<PathBuf>>,
+    /// repos on GitHub by full name, once listed
+    #[serde(skip)]
+    github_repos: Option<BTreeMap<String, GitHubRepo>>,
+    /// repos seen so far by a GitHub listing in progress
+    #[serde(skip)]
+    github_listing: Option<BTreeMap<String, GitHubRepo>>,
+}
+
+impl Model {
+    /// Repos whose id contains every space-separated word of the filter.
+    pub fn filtered<'a>(&'a self, filter: &'a str) -> impl Iterator<Item = &'a LocalRepo> + 'a {
+        let filters = filter.split(' ').collect::<Vec<_>>();
+        self.repos.iter().filter_map(move |(id, repo)| {
+            let id = id.to_string();
+            filters.iter().all(|f| id.contains(f)).then_some(repo)
+        })
+    }
+
+    /// Repos cloned from the same upstream, grouped by the case folded identity of any of their
+    /// remotes, as for comparing with GitHub.
+    ///
+    /// Only groups with more than one repo are returned, and vanished repos are ignored.
+    pub fn duplicates(&self) -> BTreeMap<RepoIdentity, Vec<&LocalRepo>> {
+        let mut groups = BTreeMap::<RepoIdentity, Vec<&LocalRepo>>::new();
+        for repo in self.repos.values().filter(|repo| !repo.vanished) {
+            let identities = repo
+                .remotes
+                .iter()
+                .filter_map(Remote::identity)
+                .map(|identity| identity.case_folded())
+                .collect::<BTreeSet<_>>();
+            for identity in identities {
+                groups.entry(identity).or_default().push(repo);
+            }
+        }
+        groups.retain(|_, repos| repos.len() > 1);
+        groups
+    }
+
+    /// Whether GitHub has been listed, so there's an inventory to compare against.
+    pub fn github_listed(&self) -> bool {
+        self.github_repos.is_some()
+    }
+
+    pub fn github_listing_in_progress(&self) -> bool {
+        self.github_listing.is_some()
+    }
+
+    /// GitHub repos of which no local repo has a remote, vanished repos not counting.
+    pub fn not_cloned(&self) -> Vec<&GitHubRepo> {
+        let cloned = self
+            .local_repos()
+            .flat_map(|repo| repo.remotes.iter().filter_map(Remote::identity))
+            .map(|identity| identity.case_folded())
+            .collect::<BTreeSet<_>>();
+        self.github_repos
+            .iter()
+            .flat_map(BTreeMap::values)
+            .filter(|github_repo| {
+                github_repo
+                    .identity()
+                    .is_some_and(|identity| !cloned.contains(&identity.case_folded()))
+            })
+            .collect()
+    }
+
+    /// Local repos with remotes for owners whose GitHub repos were listed, but which weren't
+    /// among them, so have presumably been deleted or renamed, together with those remotes.
+    pub fn gone_from_github(&self) -> Vec<(&LocalRepo, Vec<&Remote>)> {
+        let Some(github_repos) = self.github_repos.as_ref() else {
+            return Vec::default();
+        };
+        let listed = github_repos
+            .values()
+            .filter_map(GitHubRepo::identity)
+            .map(|identity| identity.case_folded())
+            .collect::<BTreeSet<_>>();
+        let owners = listed
+            .iter()
+            .map(|identity| (identity.host.as_str(), identity.owner.as_str()))
+            .collect::<BTreeSet<_>>();
+
+        self.local_repos()
+            .filter_map(|repo| {
+                let gone = repo
+                    .remotes
+                    .iter()
+                    .filter(|remote| {
+                        remote.identity().is_some_and(|identity| {
+                            let identity = identity.case_folded();
+                            owners.contains(&(identity.host.as_str(), identity.owner.as_str()))
+                                && !listed.contains(&identity)
+                        })
+                    })
+                    .collect::<Vec<_>>();
+                (!gone.is_empty()).then_some((repo, gone))
+            })
+            .collect()
+    }
+
+    /// Repos on this machine, as opposed to ssh hosts, which haven't vanished.
+    fn local_repos(&self) -> impl Iterator<Item = &LocalRepo> {
+        self.repos
+            .values()
+            .filter(|repo| repo.is_local() && !repo.vanished)
+    }
+}
+
+mod repos_as_list {
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::collections::BTreeMap;
+
+    use super::{LocalRepo, RepoId};
+
+    pub fn serialize<S>(
+        repos: &BTreeMap<RepoId, LocalRepo>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_seq(repos.values())
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<BTreeMap<RepoId, LocalRepo>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Ok(Vec::<LocalRepo>::deserialize(deserializer)?
+            .into_iter()
+            .map(|repo| (repo.id(), repo))
+            .collect())
+    }
 }
 
 pub trait UpdateModel {
     fn add_local_repo(&mut self, repo: LocalRepo);
+
+    /// Drop local repos at or below `path`.
+    fn remove_local_repos(&mut self, path: &Path);
+
+    /// Start a scan of the given host, or locally if None.
+    fn scan_started(&mut self, host: Option<&str>);
+
+    fn scan_completed(&mut self, host: Option<&str>);
+
+    /// Abandon a scan which failed part way, so found only some of the repos,
+    /// leaving the rest as they were.
+    fn scan_failed(&mut self, host: Option<&str>);
 }
 
 impl UpdateModel for Model {
     fn add_local_repo(&mut self, repo: LocalRepo) {
-        self.repos.insert(repo.path.clone(), repo);
+        if let Some(scanned) = self.scanned.get_mut(&repo.host) {
+            scanned.insert(repo.path.clone());
+        }
+        self.repos.insert(repo.id(), repo);
+    }
+
+    fn remove_local_repos(&mut self, path: &Path) {
+        self.repos
+            .retain(|id, _| !(id.is_local() && id.path.starts_with(path)));
+        if let Some(scanned) = self.scanned.get_mut(&None) {
+            scanned.retain(|repo_path| !repo_path.starts_with(path));
+        }
+    }
+
+    fn scan_started(&mut self, host: Option<&str>) {
+        self.scanned
+            .insert(host.map(str::to_string), BTreeSet::default());
+    }
+
+    /// Reconcile with what the scan found: repos on that host not seen are marked
+    /// as vanished, and any which were already vanished before the scan are dropped.
+    fn scan_completed(&mut self, host: Option<&str>) {
+        if let Some(scanned) = self.scanned.remove(&host.map(str::to_string)) {
+            self.repos.retain(|id, repo| {
+                if id.host.as_deref() != host || scanned.contains(&id.path) {
+                    true
+                } else if repo.vanished {
+                    false
+                } else {
+                    repo.vanished = true;
+                    true
+                }
+            });
+        }
     }
+
+    fn scan_failed(&mut self, host: Option<&str>) {
+        self.scanned.remove(&host.map(str::to_string));
+    }
+}
+
+/// Receiver of the repos listed by GitHub.
+pub trait UpdateGitHub {
+    fn github_listing_started(&mut self);
+
+    fn add_github_repo(&mut self, repo: GitHubRepo);
+
+    /// Replace any earlier listing with this one, unless it failed part way, so is incomplete.
+    fn github_listing_completed(&mut self, succeeded: bool);
 }
 
-#[derive(Debug)]
+impl UpdateGitHub for Model {
+    fn github_listing_started(&mut self) {
+        self.github_listing = Some(BTreeMap::default());
+    }
+
+    fn add_github_repo(&mut self, repo: GitHubRepo) {
+        if let Some(listing) = self.github_listing.as_mut() {
+            listing.insert(repo.full_name.clone(), repo);
+        }
+    }
+
+    fn github_listing_completed(&mut self, succeeded: bool) {
+        let listing = self.github_listing.take();
+        if succeeded {
+            self.github_repos = listing;
+        }
+    }
+}
+
+/// Where a repo lives, either locally or on a host reached over ssh.
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct RepoId {
+    pub host: Option<String>,
+    pub path: PathBuf,
+}
+
+impl RepoId {
+    pub fn local(path: PathBuf) -> Self {
+        Self { host: None, path }
+    }
+
+    pub fn is_local(&self) -> bool {
+        self.host.is_none()
+    }
+}
+
+/// In the manner of scp, `host:path`, or just the path if local.
+impl Display for RepoId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(host) = self.host.as_ref() {
+            write!(f, "{}:", host)?;
+        }
+        write!(f, "{}", self.path.to_string_lossy())
+    }
+}
+
+/// A repo, which despite the name may be on a remote host.
+#[derive(Serialize, Deserialize, Debug)]
 pub struct LocalRepo {
+    /// host reached over ssh, if not local
+    #[serde(default)]
+    pub host: Option<String>,
     pub path: PathBuf,
     pub remotes: Vec<Remote>,
+    #[serde(default)]
+    pub status: RepoStatus,
+    /// not found by the most recent scan
+    #[serde(default)]
+    pub vanished: bool,
 }
 
 impl LocalRepo {
-    pub fn new(path: PathBuf, remotes: Vec<Remote>) -> Self {
-        Self { path, remotes }
+    pub fn new(path: PathBuf, remotes: Vec<Remote>, status: RepoStatus) -> Self {
+        Self {
+            host: None,
+            path,
+            remotes,
+            status,
+            vanished: false,
+        }
+    }
+
+    pub fn on_host<S>(mut self, host: S) -> Self
+    where
+        S: Display,
+    {
+        self.host = Some(host.to_string());
+        self
+    }
+
+    pub fn id(&self) -> RepoId {
+        RepoId {
+            host: self.host.clone(),
+            path: self.path.clone(),
+        }
+    }
+
+    pub fn is_local(&self) -> bool {
+        self.host.is_none()
+    }
+
+    pub fn any_push_differs(&self) -> bool {
+        self.remotes.iter().any(Remote::push_differs)
+    }
+}
+
+#[derive(Default, Serialize, Deserialize, PartialEq, Eq, Debug)]
+pub struct RepoStatus {
+    pub head: Head,
+    /// tracked files modified in the worktree or index
+    pub dirty: bool,
+    pub untracked: bool,
+    pub stashes: usize,
+    /// commits (ahead, behind) the upstream branch, if any
+    pub ahead_behind: Option<(usize, usize)>,
+}
+
+impl RepoStatus {
+    /// Whether there's anything here which isn't safely on the upstream.
+    pub fn needs_attention(&self) -> bool {
+        self.dirty
+            || self.untracked
+            || self.stashes > 0
+            || self.ahead_behind.is_some_and(|(ahead, _)| ahead > 0)
+    }
+}
+
+/// Compact status in the spirit of a git-aware shell prompt, e.g. `main *? ↑2↓1 $1`
+impl Display for RepoStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.head)?;
+
+        if self.dirty || self.untracked {
+            f.write_str(" ")?;
+            if self.dirty {
+                f.write_str("*")?;
+            }
+            if self.untracked {
+                f.write_str("?")?;
+            }
+        }
+
+        match self.ahead_behind {
+            Some((0, 0)) => f.write_str(" ≡")?,
+            Some((ahead, behind)) => {
+                f.write_str(" ")?;
+                if ahead > 0 {
+                    write!(f, "↑{}", ahead)?;
+                }
+                if behind > 0 {
+                    write!(f, "↓{}", behind)?;
+                }
+            }
+            None => (),
+        }
+
+        if self.stashes > 0 {
+            write!(f, " ${}", self.stashes)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Default, Serialize, Deserialize, PartialEq, Eq, Debug)]
+pub enum Head {
+    /// checked out branch, as its short name
+    Branch(String),
+    /// branch which doesn't yet have any commits
+    Unborn(String),
+    /// abbreviated commit id
+    Detached(String),
+    #[default]
+    Unknown,
+}
+
+impl Display for Head {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use Head::*;
+        match self {
+            Branch(name) => f.write_str(name),
+            Unborn(name) => write!(f, "{} (unborn)", name),
+            Detached(id) => write!(f, "({})", id),
+            Unknown => f.write_str("?"),
+        }
     }
 }
 
-#[derive(Debug)]
+#[derive(Serialize, Deserialize, Debug)]
 pub struct Remote {
     name: String,
-    url: String,
+    fetch_url: Option<String>,
+    /// falls back to the fetch URL if no push URL is configured
+    push_url: Option<String>,
+    /// `remote.<name>.url` as configured, before any `insteadOf` rewriting
+    #[serde(default)]
+    configured_url: Option<String>,
+    /// `remote.<name>.pushurl` as configured, before any `pushInsteadOf` rewriting
+    #[serde(default)]
+    configured_push_url: Option<String>,
 }
 
 impl Remote {
-    pub fn new<S1, S2>(name: S1, url: S2) -> Self
+    pub fn new<S>(name: S, fetch_url: Option<String>, push_url: Option<String>) -> Self
     where
-        S1: Display,
-        S2: Display,
+        S: Display,
     {
         Self {
             name: name.to_string(),
-            url: url.to_string(),
+            fetch_url,
+            push_url,
+            configured_url: None,
+            configured_push_url: None,
         }
     }
 
+    pub fn with_configured(mut self, url: Option<String>, push_url: Option<String>) -> Self {
+        self.configured_url = url;
+        self.configured_push_url = push_url;
+        self
+    }
+
     pub fn name(&self) -> &str {
         &self.name
     }
 
-    pub fn url(&self) -> &str {
-        &self.url
+    pub fn fetch_url(&self) -> Option<&str> {
+        self.fetch_url.as_deref()
+    }
+
+    pub fn push_url(&self) -> Option<&str> {
+        self.push_url.as_deref()
+    }
+
+    pub fn configured_url(&self) -> Option<&str> {
+        self.configured_url.as_deref()
+    }
+
+    pub fn configured_push_url(&self) -> Option<&str> {
+        self.configured_push_url.as_deref()
+    }
+
+    /// The fetch URL parsed into its parts, if it's a valid URL.
+    pub fn parsed_fetch_url(&self) -> Option<RemoteUrl> {
+        self.fetch_url.as_deref().and_then(|url| url.parse().ok())
+    }
+
+    /// Canonical identity of the repo fetched from, so the same repo compares equal whatever URL form is used.
+    pub fn identity(&self) -> Option<RepoIdentity> {
+        self.parsed_fetch_url()
+            .as_ref()
+            .and_then(RemoteUrl::identity)
+    }
+
+    /// Whether pushing goes somewhere other than fetching.
+    pub fn push_differs(&self) -> bool {
+        self.push_url.is_some() && self.push_url != self.fetch_url
+    }
+}
+
+/// A repo as listed by GitHub.
+#[derive(Clone, Serialize, Deserialize, PartialEq, Eq, Debug)]
+pub struct GitHubRepo {
+    /// user or org
+    pub owner: String,
+    pub name: String,
+    /// `owner/name`
+    pub full_name: String,
+    pub clone_url: Option<String>,
+    pub ssh_url: Option<String>,
+    pub fork: bool,
+    pub archived: bool,
+    pub private: bool,
+    pub default_branch: Option<String>,
+    pub pushed_at: Option<DateTime<Utc>>,
+}
+
+impl GitHubRepo {
+    /// Canonical identity, to compare with that of remotes.
+    pub fn identity(&self) -> Option<RepoIdentity> {
+        self.clone_url
+            .as_deref()
+            .and_then(|url| url.parse::<RemoteUrl>().ok())
+            .as_ref()
+            .and_then(RemoteUrl::identity)
+    }
+}
+
+/// Substitution of the first occurrence of `pattern` in a remote URL.
+#[derive(Clone, Default, PartialEq, Eq, Debug)]
+pub struct UrlRewrite {
+    pub pattern: String,
+    pub replacement: String,
+}
+
+impl UrlRewrite {
+    pub fn new<S1, S2>(pattern: S1, replacement: S2) -> Self
+    where
+        S1: Display,
+        S2: Display,
+    {
+        Self {
+            pattern: pattern.to_string(),
+            replacement: replacement.to_string(),
+        }
+    }
+
+    /// Return the rewritten URL, or None if the pattern doesn't match or the rewrite changes nothing.
+    pub fn apply(&self, url: &str) -> Option<String> {
+        if self.pattern.is_empty() || !url.contains(&self.pattern) {
+            return None;
+        }
+        let rewritten = url.replacen(&self.pattern, &self.replacement, 1);
+        (rewritten != url).then_some(rewritten)
+    }
+}
+
+/// Change to a single remote of a repo.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum RemoteEdit {
+    Add {
+        name: String,
+        url: String,
+    },
+    Rename {
+        name: String,
+        new_name: String,
+    },
+    Remove {
+        name: String,
+    },
+    SetUrl {
+        name: String,
+        url: String,
+    },
+    /// setting no push URL means pushing to the fetch URL
+    SetPushUrl {
+        name: String,
+        url: Option<String>,
+    },
+}
+
+impl Display for RemoteEdit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use RemoteEdit::*;
+        match self {
+            Add { name, url } => write!(f, "add remote {} {}", name, url),
+            Rename { name, new_name } => write!(f, "rename remote {} to {}", name, new_name),
+            Remove { name } => write!(f, "remove remote {}", name),
+            SetUrl { name, url } => write!(f, "set remote {} URL to {}", name, url),
+            SetPushUrl {
+                name,
+                url: Some(url),
+            } => write!(f, "set remote {} push URL to {}", name, url),
+            SetPushUrl { name, url: None } => write!(f, "clear remote {} push URL", name),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use test_case::test_case;
+
+    fn local_repo(path: &str) -> LocalRepo {
+        LocalRepo::new(PathBuf::from(path), Vec::default(), RepoStatus::default())
+    }
+
+    fn model_with(paths: &[&str]) -> Model {
+        let mut model = Model::default();
+        for path in paths {
+            model.add_local_repo(local_repo(path));
+        }
+        model
+    }
+
+    fn vanished(model: &Model) -> Vec<&str> {
+        model
+            .repos
+            .values()
+            .filter(|repo| repo.vanished)
+            .map(|repo| repo.path.to_str().unwrap())
+            .collect()
+    }
+
+    fn ids(model: &Model) -> Vec<String> {
+        model.repos.keys().map(RepoId::to_string).collect()
+    }
+
+    #[test]
+    fn test_scan_marks_then_drops_vanished() {
+        let mut model = model_with(&["/a", "/b", "/c"]);
+
+        model.scan_started(None);
+        model.add_local_repo(local_repo("/a"));
+        model.add_local_repo(local_repo("/d"));
+        model.scan_completed(None);
+
+        assert_eq!(model.repos.len(), 4);
+        assert_eq!(vanished(&model), vec!["/b", "/c"]);
+
+        model.scan_started(None);
+        model.add_local_repo(local_repo("/b"));
+        model.scan_completed(None);
+
+        assert_eq!(ids(&model), vec!["/a", "/b", "/d"]);
+        assert_eq!(vanished(&model), vec!["/a", "/d"]);
+    }
+
+    #[test]
+    fn test_scan_only_reconciles_its_host() {
+        let mut model = model_with(&["/a"]);
+        model.add_local_repo(local_repo("/a").on_host("h1"));
+        model.add_local_repo(local_repo("/b").on_host("h2"));
+
+        model.scan_started(Some("h1"));
+        model.scan_completed(Some("h1"));
+        model.remove_local_repos(Path::new("/b"));
+
+        assert_eq!(ids(&model), vec!["/a", "h1:/a", "h2:/b"]);
+        assert_eq!(vanished(&model), vec!["/a"]);
+    }
+
+    #[test]
+    fn test_failed_scan_vanishes_nothing() {
+        let mut model = Model::default();
+        model.add_local_repo(local_repo("/a").on_host("h1"));
+        model.add_local_repo(local_repo("/b").on_host("h1"));
+
+        model.scan_started(Some("h1"));
+        model.add_local_repo(local_repo("/a").on_host("h1"));
+        model.scan_failed(Some("h1"));
+        model.scan_completed(Some("h1"));
+
+        assert_eq!(ids(&model), vec!["h1:/a", "h1:/b"]);
+        assert!(vanished(&model).is_empty());
+    }
+
+    #[test]
+    fn test_duplicates() {
+        let mut model = Model::default();
+        for (path, urls) in [
+            ("/a", vec!["git@github.com:o/r.git"]),
+            (
+                "/b",
+                vec!["https://github.com/o/r", "https://github.com/o/r.git"],
+            ),
+            ("/c", vec!["https://github.com/o/other"]),
+            (
+                "/d",
+                vec!["https://github.com/x/y", "ssh://git@github.com/o/r"],
+            ),
+            ("/e", vec!["https://github.com/O/R.git"]),
+        ] {
+            let remotes = urls
+                .into_iter()
+                .enumerate()
+                .map(|(i, url)| Remote::new(i, Some(url.to_string()), Some(url.to_string())))
+                .collect();
+            model.add_local_repo(LocalRepo::new(
+                PathBuf::from(path),
+                remotes,
+                RepoStatus::default(),
+            ));
+        }
+
+        let duplicates = model
+            .duplicates()
+            .into_iter()
+            .map(|(identity, repos)| {
+                (
+                    identity.to_string(),
+                    repos
+                        .into_iter()
+                        .map(|repo| repo.path.to_str().unwrap())
+                        .collect::<Vec<_>>(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            duplicates,
+            vec![("github.com/o/r".to_string(), vec!["/a", "/b", "/d", "/e"])]
+        );
+    }
+
+    fn github_repo(full_name: &str) -> GitHubRepo {
+        let (owner, name) = full_name.split_once('/').unwrap();
+        GitHubRepo {
+            owner: owner.to_string(),
+            name: name.to_string(),
+            full_name: full_name.to_string(),
+            clone_url: Some(format!("https://github.com/{}.git", full_name)),
+            ssh_url: Some(format!("git@github.com:{}.git", full_name)),
+            fork: false,
+            archived: false,
+            private: false,
+            default_branch: Some("main".to_string()),
+            pushed_at: None,
+        }
+    }
+
+    #[test]
+    fn test_github_correlation() {
+        let mut model = Model::default();
+        for (path, urls) in [
+            ("/a", vec!["git@github.com:Me/Widget.git"]),
+            (
+                "/b",
+                vec![
+                    "https://github.com/me/deleted",
+                    "https://github.com/other/r",
+                ],
+            ),
+            ("/c", vec!["https://gitlab.com/me/gizmo"]),
+        ] {
+            let remotes = urls
+                .into_iter()
+                .enumerate()
+                .map(|(i, url)| Remote::new(i, Some(url.to_string()), None))
+                .collect();
+            model.add_local_repo(LocalRepo::new(
+                PathBuf::from(path),
+                remotes,
+                RepoStatus::default(),
+            ));
+        }
+        // cloned only on an ssh host, so not here
+        model.add_local_repo(
+            LocalRepo::new(
+                PathBuf::from("/d"),
+                vec![Remote::new(
+                    "origin",
+                    Some("https://github.com/me/gizmo".to_string()),
+                    None,
+                )],
+                RepoStatus::default(),
+            )
+            .on_host("h1"),
+        );
+        assert!(model.gone_from_github().is_empty());
+
+        model.github_listing_started();
+        for full_name in ["me/widget", "me/gizmo", "acme/tool"] {
+            model.add_github_repo(github_repo(full_name));
+        }
+        assert!(!model.github_listed());
+        model.github_listing_completed(true);
+
+        let not_cloned = model
+            .not_cloned()
+            .into_iter()
+            .map(|repo| repo.full_name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(not_cloned, vec!["acme/tool", "me/gizmo"]);
+
+        let gone = model
+            .gone_from_github()
+            .into_iter()
+            .map(|(repo, remotes)| {
+                (
+                    repo.path.to_str().unwrap(),
+                    remotes
+                        .iter()
+                        .map(|remote| remote.name())
+                        .collect::<Vec<_>>(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(gone, vec![("/b", vec!["0"])]);
+
+        // an incomplete listing doesn't replace a complete one
+        model.github_listing_started();
+        model.github_listing_completed(false);
+        assert_eq!(model.not_cloned().len(), 2);
+    }
+
+    #[test_case(Head::Branch("main".to_string()), false, false, 0, None, "main")]
+    #[test_case(Head::Branch("main".to_string()), true, true, 0, Some((0, 0)), "main *? ≡")]
+    #[test_case(Head::Branch("dev".to_string()), false, true, 2, Some((3, 1)), "dev ? ↑3↓1 $2")]
+    #[test_case(Head::Detached("abc1234".to_string()), true, false, 0, None, "(abc1234) *")]
+    #[test_case(Head::Unborn("main".to_string()), false, false, 0, Some((0, 4)), "main (unborn) ↓4")]
+    fn test_display_status(
+        head: Head,
+        dirty: bool,
+        untracked: bool,
+        stashes: usize,
+        ahead_behind: Option<(usize, usize)>,
+        expected: &str,
+    ) {
+        let status = RepoStatus {
+            head,
+            dirty,
+            untracked,
+            stashes,
+            ahead_behind,
+        };
+        assert_eq!(status.to_string(), expected);
+    }
+
+    #[test_case(
+        "https://github.com/",
+        "git@github.com:",
+        "https://github.com/a/b",
+        Some("git@github.com:a/b")
+    )]
+    #[test_case("github.com:olduser/", "github.com:newuser/", "git@github.com:olduser/olduser.git", Some("git@github.com:newuser/olduser.git"); "first occurrence only")]
+    #[test_case("gitlab.com", "example.com", "https://github.com/a/b", None; "no match")]
+    #[test_case("", "x", "https://github.com/a/b", None; "empty pattern")]
+    #[test_case("github", "github", "https://github.com/a/b", None; "unchanged")]
+    fn test_url_rewrite(pattern: &str, replacement: &str, url: &str, expected: Option<&str>) {
+        assert_eq!(
+            UrlRewrite::new(pattern, replacement).apply(url),
+            expected.map(|s| s.to_string())
+        );
     }
 }
@@ -1,27 +1,66 @@
 use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
 use dirs::home_dir;
 use ratatui::{
-    layout::{Alignment, Constraint, Layout, Margin},
-    style::Modifier,
+    layout::{Alignment, Constraint, Layout, Margin, Rect},
+    style::{Color, Modifier, Style},
     widgets::{
-        Block, BorderType, Paragraph, Row, Scrollbar, ScrollbarOrientation, ScrollbarState, Table,
+        Block, BorderType, Cell, Paragraph, Row, Scrollbar, ScrollbarOrientation, ScrollbarState,
+        Table,
     },
     Frame,
 };
 use std::{
     borrow::Cow,
     cmp::{max, min},
+    collections::BTreeSet,
     default::Default,
     path::{Path, PathBuf},
 };
+use tracing::warn;
 use tui_input::{backend::crossterm::EventHandler, Input};
 
 use crate::{
-    config::Config,
-    model::{LocalRepo, Model, UpdateModel},
+    cache,
+    config::{CloneUrl, Config},
+    model::{GitHubRepo, LocalRepo, Model, RepoId, UpdateGitHub, UpdateModel},
+    service::{
+        self,
+        exec::{CloneJob, ExecResult, UpdateClone, UpdateExec, UpdateFetch},
+        filesystem,
+        ssh::AskUser,
+        Notify,
+    },
+    ssh::Question,
     util::common_prefix,
 };
 
+mod clone;
+mod duplicates;
+mod exec;
+mod fetch;
+mod github;
+mod questions;
+mod remote_editor;
+mod rewrite_urls;
+use clone::Clones;
+use duplicates::Duplicates;
+use exec::Exec;
+use fetch::{Fetch, FetchState};
+use github::GitHub;
+use questions::Questions;
+use remote_editor::RemoteEditor;
+use rewrite_urls::RewriteUrls;
+
+/// What the presenter needs done on its behalf in response to a key.
+pub enum Action {
+    Quit,
+    /// scan the filesystem and ssh hosts again
+    Rescan,
+    Filesystem(filesystem::Request),
+    Exec(service::exec::Request),
+    GitHub(service::github::Request),
+}
+
 #[derive(Debug)]
 pub struct Presenter {
     config: Config,
@@ -30,11 +69,36 @@ pub struct Presenter {
     repo_filter_input: Input,
     view_height: usize,
     selected: Option<Selected>,
+    mode: Mode,
+    message: Option<Message>,
+    /// kept when not shown, so results of a running command aren't lost
+    exec: Exec,
+    /// most recent fetch, if any
+    fetch: Option<Fetch>,
+    /// take precedence over the mode until answered
+    questions: Questions,
+    clones: Clones,
+}
+
+#[derive(Debug)]
+enum Mode {
+    Repos,
+    RewriteUrls(RewriteUrls),
+    RemoteEditor(RemoteEditor),
+    Duplicates(Duplicates),
+    GitHub(GitHub),
+    Exec,
+    FetchFailures,
+}
+
+#[derive(Debug)]
+enum Message {
+    Info(String),
+    Error(String),
 }
 
 impl Presenter {
-    pub fn new(config: &Config) -> Self {
-        let model = Model::default();
+    pub fn new(config: &Config, model: Model) -> Self {
         Self {
             config: config.clone(),
             home_dir: home_dir().map(|p| p.to_string_lossy().into_owned()),
@@ -42,15 +106,174 @@ impl Presenter {
             repo_filter_input: Input::default(),
             view_height: 1,
             selected: None,
+            mode: Mode::Repos,
+            message: None,
+            exec: Exec::default(),
+            fetch: None,
+            questions: Questions::default(),
+            clones: Clones::default(),
         }
     }
 
-    pub fn handle_key(&mut self, ev: KeyEvent) -> bool {
+    pub fn handle_key(&mut self, ev: KeyEvent) -> Option<Action> {
         if is_quit(&ev) {
-            return true;
+            return Some(Action::Quit);
+        }
+        self.message = None;
+
+        if !self.questions.is_empty() {
+            self.questions.handle_key(ev);
+            return None;
         }
 
-        if ev.code == KeyCode::Esc {
+        match &mut self.mode {
+            Mode::Repos => self.handle_repos_key(ev),
+            Mode::RewriteUrls(rewrite_urls) => match rewrite_urls.handle_key(ev) {
+                Some(rewrite_urls::Outcome::Apply(rewrite)) => {
+                    let paths = rewrite_urls
+                        .preview(&self.model)
+                        .into_iter()
+                        .map(|rewritten| rewritten.repo.path.clone())
+                        .collect::<BTreeSet<_>>();
+                    if paths.is_empty() {
+                        self.message = Some(Message::Error("no remote URLs match".to_string()));
+                        None
+                    } else {
+                        self.mode = Mode::Repos;
+                        Some(Action::Filesystem(filesystem::Request::RewriteRemoteUrls(
+                            rewrite,
+                            paths.into_iter().collect(),
+                        )))
+                    }
+                }
+                Some(rewrite_urls::Outcome::Cancel) => {
+                    self.mode = Mode::Repos;
+                    None
+                }
+                None => None,
+            },
+            Mode::RemoteEditor(remote_editor) => {
+                let id = RepoId::local(remote_editor.path().to_path_buf());
+                let Some(repo) = self.model.repos.get(&id) else {
+                    // removed while editing, such as by the filesystem watcher
+                    self.mode = Mode::Repos;
+                    self.message = Some(Message::Error(format!(
+                        "{} is no longer a repo",
+                        id.path.display()
+                    )));
+                    return None;
+                };
+                match remote_editor.handle_key(ev, repo) {
+                    Some(remote_editor::Outcome::Edit(edit)) => Some(Action::Filesystem(
+                        filesystem::Request::EditRemote(repo.path.clone(), edit),
+                    )),
+                    Some(remote_editor::Outcome::Close) => {
+                        self.mode = Mode::Repos;
+                        None
+                    }
+                    None => None,
+                }
+            }
+            Mode::Duplicates(duplicates) => match duplicates.handle_key(ev, &self.model) {
+                Some(duplicates::Outcome::Select(id)) => {
+                    self.mode = Mode::Repos;
+                    // clear the filter so the repo is sure to be visible
+                    self.repo_filter_input.reset();
+                    self.selected = Some(Selected::new(id, 0));
+                    None
+                }
+                Some(duplicates::Outcome::Close) => {
+                    self.mode = Mode::Repos;
+                    None
+                }
+                None => None,
+            },
+            Mode::GitHub(github) => match github.handle_key(ev, &self.model) {
+                Some(github::Outcome::Select(id)) => {
+                    self.mode = Mode::Repos;
+                    // clear the filter so the repo is sure to be visible
+                    self.repo_filter_input.reset();
+                    self.selected = Some(Selected::new(id, 0));
+                    None
+                }
+                Some(github::Outcome::Refresh) => self.list_github(),
+                Some(github::Outcome::Clone(github_repos)) => self.clone_github_repos(github_repos),
+                Some(github::Outcome::Close) => {
+                    self.mode = Mode::Repos;
+                    None
+                }
+                None => None,
+            },
+            Mode::Exec => match self.exec.handle_key(ev) {
+                Some(exec::Outcome::Run(command)) => {
+                    let paths = self.filtered_paths();
+                    if paths.is_empty() {
+                        self.message = Some(Message::Error("no repos match filter".to_string()));
+                        None
+                    } else {
+                        self.exec.started(command.clone(), &paths);
+                        Some(Action::Exec(service::exec::Request::Run(command, paths)))
+                    }
+                }
+                Some(exec::Outcome::Invalid(e)) => {
+                    self.message = Some(Message::Error(e));
+                    None
+                }
+                Some(exec::Outcome::Close) => {
+                    self.mode = Mode::Repos;
+                    None
+                }
+                None => None,
+            },
+            Mode::FetchFailures => match self.fetch.as_mut()?.handle_key(ev) {
+                Some(fetch::Outcome::Close) => {
+                    self.mode = Mode::Repos;
+                    None
+                }
+                None => None,
+            },
+        }
+    }
+
+    fn handle_repos_key(&mut self, ev: KeyEvent) -> Option<Action> {
+        if is_ctrl(&ev, 'r') {
+            self.mode = Mode::RewriteUrls(RewriteUrls::default());
+        } else if is_ctrl(&ev, 't') {
+            if let Some(selected) = self.selected.as_ref() {
+                if selected.id.is_local() {
+                    self.mode = Mode::RemoteEditor(RemoteEditor::new(selected.id.path.clone()));
+                } else {
+                    self.message = Some(Message::Error(format!(
+                        "can't edit remotes of {}, only local repos",
+                        selected.id
+                    )));
+                }
+            }
+        } else if is_ctrl(&ev, 'x') {
+            // show any command still running, otherwise prompt for a new one
+            if !self.exec.is_running() {
+                self.exec = Exec::default();
+            }
+            self.mode = Mode::Exec;
+        } else if is_ctrl(&ev, 'g') {
+            return self.fetch_filtered();
+        } else if is_ctrl(&ev, 'o') {
+            if self.fetch.as_ref().is_some_and(Fetch::has_failures) {
+                self.mode = Mode::FetchFailures;
+            } else {
+                self.message = Some(Message::Error("no fetch failures".to_string()));
+            }
+        } else if is_ctrl(&ev, 'd') {
+            self.mode = Mode::Duplicates(Duplicates::default());
+        } else if is_ctrl(&ev, 'n') {
+            self.mode = Mode::GitHub(GitHub::default());
+            // list on first use, rather than at every startup
+            if !self.model.github_listed() && !self.model.github_listing_in_progress() {
+                return self.list_github();
+            }
+        } else if ev.code == KeyCode::F(5) {
+            return Some(Action::Rescan);
+        } else if ev.code == KeyCode::Esc {
             self.repo_filter_input.reset();
         } else if ev.code == KeyCode::Up {
             self.scroll(-1);
@@ -66,7 +289,98 @@ impl Presenter {
             self.repo_filter_input.handle_event(&Event::Key(ev));
         }
 
-        false
+        None
+    }
+
+    fn filtered_paths(&self) -> Vec<PathBuf> {
+        self.model
+            .filtered(self.repo_filter_input.value())
+            .filter(|repo| repo.is_local() && !repo.vanished)
+            .map(|repo| repo.path.clone())
+            .collect()
+    }
+
+    fn fetch_filtered(&mut self) -> Option<Action> {
+        if self.fetch.as_ref().is_some_and(Fetch::is_running) {
+            self.message = Some(Message::Error("fetch already in progress".to_string()));
+            return None;
+        }
+
+        let paths = self.filtered_paths();
+        if paths.is_empty() {
+            self.message = Some(Message::Error("no repos match filter".to_string()));
+            None
+        } else {
+            self.fetch = Some(Fetch::new(&paths));
+            Some(Action::Exec(service::exec::Request::Fetch(paths)))
+        }
+    }
+
+    fn list_github(&mut self) -> Option<Action> {
+        if self.model.github_listing_in_progress() {
+            self.message = Some(Message::Error(
+                "GitHub listing already in progress".to_string(),
+            ));
+            None
+        } else {
+            Some(Action::GitHub(service::github::Request::List))
+        }
+    }
+
+    fn clone_github_repos(&mut self, github_repos: Vec<GitHubRepo>) -> Option<Action> {
+        let mut jobs = Vec::new();
+        for github_repo in github_repos {
+            let url = match self.config.github.clone_url {
+                CloneUrl::Https => github_repo.clone_url.clone(),
+                CloneUrl::Ssh => github_repo.ssh_url.clone(),
+            };
+            let (Some(url), Some(identity)) = (url, github_repo.identity()) else {
+                warn!("no URL to clone {}", &github_repo.full_name);
+                continue;
+            };
+            match self.config.github.clone_path(
+                &identity.host,
+                &github_repo.owner,
+                &github_repo.name,
+            ) {
+                Ok(Some(path)) => {
+                    if !self.clones.is_cloning(&path) {
+                        jobs.push(CloneJob { url, path });
+                    }
+                }
+                Ok(None) => {
+                    self.message = Some(Message::Error(
+                        "set github.clone-path in the config to clone".to_string(),
+                    ));
+                    return None;
+                }
+                Err(e) => {
+                    // was validated when reading config
+                    self.message = Some(Message::Error(e.to_string()));
+                    return None;
+                }
+            }
+        }
+
+        if jobs.is_empty() {
+            self.message = Some(Message::Error("nothing to clone".to_string()));
+            None
+        } else {
+            self.clones
+                .started(&jobs.iter().map(|job| job.path.clone()).collect::<Vec<_>>());
+            Some(Action::Exec(service::exec::Request::CloneRepos(jobs)))
+        }
+    }
+
+    /// State of any clone of the GitHub repo to where it would be cloned.
+    fn clone_state(&self, github_repo: &GitHubRepo) -> Option<&clone::CloneState> {
+        let host = github_repo.identity()?.host;
+        let path = self
+            .config
+            .github
+            .clone_path(&host, &github_repo.owner, &github_repo.name)
+            .ok()??;
+        self.clones.state(&path)
     }
 
     fn scroll(&mut self, offset: isize) {
@@ -110,32 +424,22 @@ impl Presenter {
             };
 
             self.selected = Some(Selected::new(
-                filtered_repos[u_scrolled].path.clone(),
+                filtered_repos[u_scrolled].id(),
                 min(u_view_scrolled, u_scrolled),
             ));
         }
     }
 
     fn filtered_repos(&self) -> (Vec<&LocalRepo>, Option<usize>) {
-        let filters = self
-            .repo_filter_input
-            .value()
-            .split(' ')
-            .collect::<Vec<_>>();
         let mut u_selected = None;
         let repos = self
             .model
-            .repos
-            .iter()
-            .map(|(path, repo)| (path.to_string_lossy(), repo))
-            .filter(move |(s, _)| filters.iter().all(|f| s.contains(f)))
+            .filtered(self.repo_filter_input.value())
             .enumerate()
-            .map(|(i, (_, repo))| {
-                if self
-                    .selected
-                    .as_ref()
-                    .is_some_and(|selected| selected.path == repo.path)
-                {
+            .map(|(i, repo)| {
+                if self.selected.as_ref().is_some_and(|selected| {
+                    selected.id.path == repo.path && selected.id.host == repo.host
+                }) {
                     u_selected = Some(i);
                 }
 
@@ -147,8 +451,66 @@ impl Presenter {
     }
 
     pub fn render(&mut self, frame: &mut Frame) {
+        let layout =
+            Layout::vertical(vec![Constraint::Fill(1), Constraint::Length(1)]).split(frame.area());
+
+        match &self.mode {
+            Mode::Repos => self.render_repos(frame, layout[0]),
+            Mode::RewriteUrls(rewrite_urls) => rewrite_urls.render(
+                frame,
+                layout[0],
+                &rewrite_urls.preview(&self.model),
+                |path| self.display_path(path),
+            ),
+            Mode::RemoteEditor(remote_editor) => {
+                if let Some(repo) = self
+                    .model
+                    .repos
+                    .get(&RepoId::local(remote_editor.path().to_path_buf()))
+                {
+                    remote_editor.render(frame, layout[0], repo, &self.display_path(&repo.path))
+                }
+            }
+            Mode::Duplicates(duplicates) => {
+                duplicates.render(frame, layout[0], &self.model.duplicates(), |repo| {
+                    self.display_repo(repo)
+                })
+            }
+            Mode::GitHub(github) => github.render(
+                frame,
+                layout[0],
+                &self.model,
+                |repo| self.display_repo(repo),
+                |github_repo| self.clone_state(github_repo),
+            ),
+            Mode::Exec => self
+                .exec
+                .render(frame, layout[0], |path| self.display_path(path)),
+            Mode::FetchFailures => {
+                if let Some(fetch) = self.fetch.as_ref() {
+                    fetch.render(frame, layout[0], |path| self.display_path(path))
+                }
+            }
+        }
+
+        self.questions.render(frame, layout[0]);
+
+        if let Some(message) = self.message.as_ref() {
+            frame.render_widget(
+                match message {
+                    Message::Info(text) => Paragraph::new(text.as_str()),
+                    Message::Error(text) => {
+                        Paragraph::new(text.as_str()).style(Style::new().fg(Color::Red))
+                    }
+                },
+                layout[1],
+            );
+        }
+    }
+
+    fn render_repos(&mut self, frame: &mut Frame, area: Rect) {
         let main_layout =
-            Layout::vertical(vec![Constraint::Length(1), Constraint::Fill(1)]).split(frame.area());
+            Layout::vertical(vec![Constraint::Length(1), Constraint::Fill(1)]).split(area);
         let repo_layout = Layout::horizontal(vec![Constraint::Length(1), Constraint::Fill(1)])
             .split(main_layout[1]);
 
@@ -195,12 +557,15 @@ impl Presenter {
             .skip(skip)
             .take(max_visible)
             .map(|(i, repo)| {
-                let modifier = if u_selected.is_some_and(|selected| i == selected) {
+                let mut modifier = if u_selected.is_some_and(|selected| i == selected) {
                     Modifier::REVERSED
                 } else {
                     Modifier::default()
                 };
-                let display_path = self.display_path(&repo.path);
+                if repo.vanished {
+                    modifier |= Modifier::CROSSED_OUT;
+                }
+                let display_path = self.display_repo(repo);
                 let (collapsed_display_path, len) =
                     if let Some((previous_path, previous_len)) = &previous_display {
                         let n = common_prefix::len_ending(
@@ -220,14 +585,46 @@ impl Presenter {
                     };
                 previous_display = Some((display_path.clone(), len));
 
+                let status_style = if repo.status.needs_attention() {
+                    Style::new().fg(Color::Yellow)
+                } else {
+                    Style::new()
+                };
+                let status = match self
+                    .fetch
+                    .as_ref()
+                    .and_then(|fetch| fetch.state(&repo.id()))
+                {
+                    Some(FetchState::Pending) => Cell::from(format!("… {}", repo.status)),
+                    Some(FetchState::Fetching) => Cell::from(format!("⟳ {}", repo.status)),
+                    Some(FetchState::Failed) => {
+                        Cell::from(format!("✗ {}", repo.status)).style(Style::new().fg(Color::Red))
+                    }
+                    Some(FetchState::Fetched) | None => {
+                        Cell::from(repo.status.to_string()).style(status_style)
+                    }
+                };
+
                 Row::new([
-                    if self.config.view.collapse_paths {
+                    Cell::from(if self.config.view.collapse_paths {
                         collapsed_display_path
                     } else {
                         display_path
+                    })
+                    .style(if repo.is_local() {
+                        Style::new()
+                    } else {
+                        // mark repos on ssh hosts
+                        Style::new().fg(Color::Cyan)
+                    }),
+                    if repo.any_push_differs() {
+                        // flag that some remote pushes elsewhere than it fetches from
+                        Cell::from(format!("{} ≠", repo.remotes.len()))
+                            .style(Style::new().fg(Color::Yellow))
+                    } else {
+                        Cell::from(repo.remotes.len().to_string())
                     },
-                    Cow::Owned(repo.remotes.len().to_string()),
-                    Cow::Borrowed(""),
+                    status,
                 ])
                 .style(modifier)
             })
@@ -247,7 +644,7 @@ impl Presenter {
             Table::new(rows, table_widths).block(
                 Block::bordered()
                     .title(format!(
-                        " filtered {}/{} local repos ",
+                        " filtered {}/{} repos ",
                         n_filtered_repos,
                         self.model.repos.len()
                     ))
@@ -258,6 +655,14 @@ impl Presenter {
         )
     }
 
+    /// Local repos by path, others by `host:path`.
+    fn display_repo<'a>(&self, repo: &'a LocalRepo) -> Cow<'a, str> {
+        match repo.host.as_ref() {
+            Some(host) => Cow::Owned(format!("{}:{}", host, repo.path.to_string_lossy())),
+            None => self.display_path(&repo.path),
+        }
+    }
+
     fn display_path<'a>(&self, path: &'a Path) -> Cow<'a, str> {
         let path = path.to_string_lossy();
         if let Some(home_dir) = self.home_dir.as_ref() {
@@ -271,13 +676,22 @@ impl Presenter {
     // TODO - this shouldn't be inline perhaps?
     fn open_git_client(&mut self) {
         if let Some(selected) = self.selected.as_ref() {
-            let path = selected.path.canonicalize().unwrap();
+            if !selected.id.is_local() {
+                self.message = Some(Message::Error(format!(
+                    "can't open git client on {}, only local repos",
+                    selected.id
+                )));
+                return;
+            }
+            let path = selected.id.path.canonicalize().unwrap();
             let args = self
                 .config
                 .git_client
                 .format_args(path.to_string_lossy())
-                .unwrap(); // was validated when reading config
-            std::process::Command::new(&self.config.git_client.command)
+                // was validated when reading config
+                .unwrap();
+            // unlike with std, tokio reaps the child in the background once it exits
+            tokio::process::Command::new(&self.config.git_client.command)
                 .args(args)
                 .spawn()
                 .unwrap_or_else(|e| {
@@ -294,22 +708,138 @@ impl Presenter {
     // }
 }
 
+impl Notify for Presenter {
+    fn info(&mut self, message: String) {
+        self.message = Some(Message::Info(message));
+    }
+
+    fn error(&mut self, message: String) {
+        self.message = Some(Message::Error(message));
+    }
+}
+
+impl UpdateExec for Presenter {
+    fn exec_result(&mut self, result: ExecResult) {
+        self.exec.add_result(result);
+    }
+
+    fn exec_completed(&mut self) {
+        if let Some(summary) = self.exec.completed() {
+            self.info(summary);
+        }
+    }
+}
+
+impl UpdateClone for Presenter {
+    fn cloning(&mut self, path: PathBuf) {
+        self.clones.cloning(path);
+    }
+
+    fn clone_progress(&mut self, path: PathBuf, progress: String) {
+        self.clones.progress(path, progress);
+    }
+
+    fn cloned(&mut self, result: ExecResult, repo: Option<LocalRepo>) {
+        self.clones.cloned(&result);
+        if let Some(repo) = repo {
+            self.add_local_repo(repo);
+        }
+    }
+
+    fn clone_completed(&mut self) {
+        match self.clones.completed() {
+            Some((summary, true)) => self.error(summary),
+            Some((summary, false)) => self.info(summary),
+            None => (),
+        }
+    }
+}
+
+impl UpdateFetch for Presenter {
+    fn fetching(&mut self, path: PathBuf) {
+        if let Some(fetch) = self.fetch.as_mut() {
+            fetch.fetching(path);
+        }
+    }
+
+    fn fetched(&mut self, result: ExecResult, repo: LocalRepo) {
+        self.add_local_repo(repo);
+        if let Some(fetch) = self.fetch.as_mut() {
+            fetch.fetched(result);
+        }
+    }
+
+    fn fetch_completed(&mut self) {
+        if let Some(fetch) = self.fetch.as_mut() {
+            let summary = fetch.completed();
+            if fetch.has_failures() {
+                self.error(format!("{}, Ctrl-O lists them", summary));
+                if matches!(self.mode, Mode::Repos) {
+                    self.mode = Mode::FetchFailures;
+                }
+            } else {
+                self.info(summary);
+            }
+        }
+    }
+}
+
+impl AskUser for Presenter {
+    fn ask(&mut self, question: Question) {
+        self.questions.ask(question);
+    }
+}
+
 impl UpdateModel for Presenter {
     fn add_local_repo(&mut self, repo: LocalRepo) {
         self.model.add_local_repo(repo);
         // self.model_updated();
     }
+
+    fn remove_local_repos(&mut self, path: &Path) {
+        self.model.remove_local_repos(path);
+    }
+
+    fn scan_started(&mut self, host: Option<&str>) {
+        self.model.scan_started(host);
+    }
+
+    fn scan_completed(&mut self, host: Option<&str>) {
+        self.model.scan_completed(host);
+
+        if let Err(e) = cache::write_model(&self.model) {
+            warn!("failed to write model cache: {}", e);
+        }
+    }
+
+    fn scan_failed(&mut self, host: Option<&str>) {
+        self.model.scan_failed(host);
+    }
+}
+
+impl UpdateGitHub for Presenter {
+    fn github_listing_started(&mut self) {
+        self.model.github_listing_started();
+    }
+
+    fn add_github_repo(&mut self, repo: GitHubRepo) {
+        self.model.add_github_repo(repo);
+    }
+
+    fn github_listing_completed(&mut self, succeeded: bool) {
+        self.model.github_listing_completed(succeeded);
+    }
 }
 
 #[derive(Debug)]
 struct Selected {
-    path: PathBuf,
+    id: RepoId,
     u_view: usize,
 }
 
 impl Selected {
-    fn new(path: PathBuf, u_view: usize) -> Self {
-        Self { path, u_view }
+    fn new(id: RepoId, u_view: usize) -> Self {
+        Self { id, u_view }
     }
 }
 
@@ -317,6 +847,10 @@ fn is_quit(key_event: &KeyEvent) -> bool {
     matches!(key_event.code, KeyCode::Char('c') | KeyCode::Char('C') if key_event.modifiers == KeyModifiers::CONTROL)
 }
 
+fn is_ctrl(key_event: &KeyEvent, c: char) -> bool {
+    key_event.code == KeyCode::Char(c) && key_event.modifiers == KeyModifiers::CONTROL
+}
+
 /// constrain i in [0, limit), panic if limit is zero
 fn constrained_by(i: isize, limit: usize) -> usize {
     assert!(limit > 0);
@@ -0,0 +1,132 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use crate::service::exec::ExecResult;
+
+/// Repos being cloned, or which failed to clone, by where they are cloned to.
+///
+/// Those cloned successfully are dropped, being in the model from then on.
+#[derive(Default, Debug)]
+pub struct Clones {
+    states: BTreeMap<PathBuf, CloneState>,
+    /// since all clones were last done
+    n_cloned: usize,
+    n_failed: usize,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum CloneState {
+    Pending,
+    /// with git's most recent progress report
+    Cloning(String),
+    Failed(String),
+}
+
+impl Clones {
+    pub fn started(&mut self, paths: &[PathBuf]) {
+        for path in paths {
+            self.states.insert(path.clone(), CloneState::Pending);
+        }
+    }
+
+    pub fn state(&self, path: &Path) -> Option<&CloneState> {
+        self.states.get(path)
+    }
+
+    /// Whether a clone to the path is pending or in progress.
+    pub fn is_cloning(&self, path: &Path) -> bool {
+        matches!(
+            self.state(path),
+            Some(CloneState::Pending | CloneState::Cloning(_))
+        )
+    }
+
+    pub fn cloning(&mut self, path: PathBuf) {
+        self.states.insert(path, CloneState::Cloning(String::new()));
+    }
+
+    pub fn progress(&mut self, path: PathBuf, progress: String) {
+        self.states.insert(path, CloneState::Cloning(progress));
+    }
+
+    pub fn cloned(&mut self, result: &ExecResult) {
+        if result.success() {
+            self.states.remove(&result.path);
+            self.n_cloned += 1;
+        } else {
+            // git's last word is usually the reason, such as the repo not being found
+            let reason = result
+                .stderr
+                .lines()
+                .rev()
+                .map(str::trim)
+                .find(|line| !line.is_empty())
+                .map(str::to_string)
+                .unwrap_or_else(|| result.status.to_string());
+            self.states
+                .insert(result.path.clone(), CloneState::Failed(reason));
+            self.n_failed += 1;
+        }
+    }
+
+    /// A summary, and whether any failed, once there are no more clones in progress.
+    pub fn completed(&mut self) -> Option<(String, bool)> {
+        if self
+            .states
+            .values()
+            .any(|state| !matches!(state, CloneState::Failed(_)))
+        {
+            return None;
+        }
+        let summary = format!("cloned {} repos, {} failed", self.n_cloned, self.n_failed);
+        let any_failed = self.n_failed > 0;
+        self.n_cloned = 0;
+        self.n_failed = 0;
+        Some((summary, any_failed))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::service::exec::ExecStatus;
+
+    fn result(path: &str, code: i32, stderr: &str) -> ExecResult {
+        ExecResult {
+            path: PathBuf::from(path),
+            status: ExecStatus::Exited(code),
+            stdout: String::new(),
+            stderr: stderr.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_clones() {
+        let mut clones = Clones::default();
+        clones.started(&[PathBuf::from("/a"), PathBuf::from("/b")]);
+        clones.cloning(PathBuf::from("/a"));
+        assert!(clones.is_cloning(Path::new("/b")));
+
+        clones.cloned(&result("/a", 0, ""));
+        assert_eq!(clones.state(Path::new("/a")), None);
+        assert_eq!(clones.completed(), None);
+
+        clones.cloned(&result(
+            "/b",
+            128,
+            "Cloning into '/b'...\nfatal: repository not found\n",
+        ));
+        assert_eq!(
+            clones.state(Path::new("/b")),
+            Some(&CloneState::Failed(
+                "fatal: repository not found".to_string()
+            ))
+        );
+        assert_eq!(
+            clones.completed(),
+            Some(("cloned 1 repos, 1 failed".to_string(), true))
+        );
+    }
+}
@@ -0,0 +1,115 @@
+use crossterm::event::{KeyCode, KeyEvent};
+use ratatui::{
+    layout::{Alignment, Constraint, Layout, Rect},
+    style::{Color, Modifier, Style, Stylize},
+    widgets::{Block, BorderType, Cell, Paragraph, Row, Table},
+    Frame,
+};
+use std::{borrow::Cow, collections::BTreeMap};
+
+use crate::{
+    model::{LocalRepo, Model, RepoId},
+    remote_url::RepoIdentity,
+};
+
+/// Repos cloned from the same upstream, side by side.
+#[derive(Default, Debug)]
+pub struct Duplicates {
+    /// index into the repos of all groups, in order
+    selected: usize,
+}
+
+pub enum Outcome {
+    /// go to the repo in the main view
+    Select(RepoId),
+    Close,
+}
+
+impl Duplicates {
+    pub fn handle_key(&mut self, ev: KeyEvent, model: &Model) -> Option<Outcome> {
+        let n_repos = model.duplicates().values().map(Vec::len).sum::<usize>();
+        self.selected = self.selected.min(n_repos.saturating_sub(1));
+
+        match ev.code {
+            KeyCode::Esc => return Some(Outcome::Close),
+            KeyCode::Up => self.selected = self.selected.saturating_sub(1),
+            KeyCode::Down => self.selected = (self.selected + 1).min(n_repos.saturating_sub(1)),
+            KeyCode::Enter => {
+                return model
+                    .duplicates()
+                    .into_values()
+                    .flatten()
+                    .nth(self.selected)
+                    .map(|repo| Outcome::Select(repo.id()))
+            }
+            _ => (),
+        }
+        None
+    }
+
+    pub fn render<F>(
+        &self,
+        frame: &mut Frame,
+        area: Rect,
+        duplicates: &BTreeMap<RepoIdentity, Vec<&LocalRepo>>,
+        display_repo: F,
+    ) where
+        F: Fn(&LocalRepo) -> Cow<'_, str>,
+    {
+        let layout = Layout::vertical(vec![Constraint::Fill(1), Constraint::Length(1)]).split(area);
+
+        let mut rows = Vec::new();
+        let mut i = 0;
+        let mut selected_row = 0;
+        for (identity, repos) in duplicates.iter() {
+            rows.push(Row::new([Cell::from(identity.to_string()).bold()]));
+            for repo in repos {
+                if i == self.selected {
+                    selected_row = rows.len();
+                }
+                let status_style = if repo.status.needs_attention() {
+                    Style::new().fg(Color::Yellow)
+                } else {
+                    Style::new()
+                };
+                rows.push(
+                    Row::new([
+                        Cell::from(format!("  {}", display_repo(repo))),
+                        Cell::from(repo.status.to_string()).style(status_style),
+                    ])
+                    .style(if i == self.selected {
+                        Style::new().add_modifier(Modifier::REVERSED)
+                    } else {
+                        Style::new()
+                    }),
+                );
+                i += 1;
+            }
+        }
+
+        // keep the selected repo in view, along with its group heading where possible
+        const BORDER_WASTAGE: usize = 2;
+        let view_height = (layout[0].height as usize).saturating_sub(BORDER_WASTAGE);
+        let skip = (selected_row + 1).saturating_sub(view_height);
+
+        frame.render_widget(
+            Table::new(
+                rows.into_iter().skip(skip),
+                [Constraint::Ratio(2, 3), Constraint::Ratio(1, 3)],
+            )
+            .block(
+                Block::bordered()
+                    .title(format!(
+                        " {} upstreams cloned more than once, {} repos ",
+                        duplicates.len(),
+                        i
+                    ))
+                    .title_alignment(Alignment::Center)
+                    .border_type(BorderType::Rounded),
+            ),
+            layout[0],
+        );
+
+        frame.render_widget(Paragraph::new("Enter go to repo  Esc back"), layout[1]);
+    }
+}
@@ -0,0 +1,208 @@
+use crossterm::event::{Event, KeyCode, KeyEvent};
+use ratatui::{
+    layout::{Alignment, Constraint, Layout, Rect},
+    style::{Color, Modifier, Style},
+    text::{Line, Span, Text},
+    widgets::{Block, BorderType, Cell, Paragraph, Row, Table, Wrap},
+    Frame,
+};
+use std::{
+    borrow::Cow,
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+use tui_input::{backend::crossterm::EventHandler, Input};
+
+use crate::service::exec::{ExecCommand, ExecResult};
+
+/// Running a command across the filtered repos, and showing the results.
+#[derive(Default, Debug)]
+pub struct Exec {
+    command_input: Input,
+    running: Option<Running>,
+}
+
+#[derive(Debug)]
+struct Running {
+    command: ExecCommand,
+    /// result for each repo, or None while pending
+    results: BTreeMap<PathBuf, Option<ExecResult>>,
+    selected: usize,
+    completed: bool,
+}
+
+pub enum Outcome {
+    Run(ExecCommand),
+    /// the command line doesn't make sense
+    Invalid(String),
+    Close,
+}
+
+impl Exec {
+    pub fn handle_key(&mut self, ev: KeyEvent) -> Option<Outcome> {
+        match self.running.as_mut() {
+            None => match ev.code {
+                KeyCode::Esc => Some(Outcome::Close),
+                KeyCode::Enter => match ExecCommand::parse(self.command_input.value()) {
+                    Ok(command) => command.map(|command| match command.format_args("") {
+                        Ok(_) => Outcome::Run(command),
+                        Err(e) => Outcome::Invalid(e.to_string()),
+                    }),
+                    Err(e) => Some(Outcome::Invalid(e.to_string())),
+                },
+                _ => {
+                    self.command_input.handle_event(&Event::Key(ev));
+                    None
+                }
+            },
+            Some(running) => {
+                let last = running.results.len().saturating_sub(1);
+                match ev.code {
+                    KeyCode::Esc => return Some(Outcome::Close),
+                    KeyCode::Up => running.selected = running.selected.saturating_sub(1),
+                    KeyCode::Down => running.selected = (running.selected + 1).min(last),
+                    _ => (),
+                }
+                None
+            }
+        }
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.running
+            .as_ref()
+            .is_some_and(|running| !running.completed)
+    }
+
+    pub fn started(&mut self, command: ExecCommand, paths: &[PathBuf]) {
+        self.running = Some(Running {
+            command,
+            results: paths.iter().map(|path| (path.clone(), None)).collect(),
+            selected: 0,
+            completed: false,
+        });
+    }
+
+    pub fn add_result(&mut self, result: ExecResult) {
+        if let Some(running) = self.running.as_mut() {
+            if let Some(pending) = running.results.get_mut(&result.path) {
+                *pending = Some(result);
+            }
+        }
+    }
+
+    /// Mark as completed, returning a summary.
+    pub fn completed(&mut self) -> Option<String> {
+        let running = self.running.as_mut()?;
+        running.completed = true;
+        let n_failed = running
+            .results
+            .values()
+            .filter(|result| result.as_ref().is_some_and(|result| !result.success()))
+            .count();
+        Some(format!(
+            "ran {} in {} repos, {} failed",
+            running.command,
+            running.results.len(),
+            n_failed
+        ))
+    }
+
+    pub fn render<F>(&self, frame: &mut Frame, area: Rect, display_path: F)
+    where
+        F: Fn(&Path) -> Cow<'_, str>,
+    {
+        let running = match self.running.as_ref() {
+            None => {
+                let layout =
+                    Layout::vertical(vec![Constraint::Length(1), Constraint::Fill(1)]).split(area);
+                frame.render_widget(
+                    Paragraph::new(Line::from(vec![
+                        Span::styled("command:", Style::new().add_modifier(Modifier::REVERSED)),
+                        Span::raw(" "),
+                        Span::raw(self.command_input.value()),
+                    ])),
+                    layout[0],
+                );
+                frame.render_widget(
+                    Paragraph::new(
+                        "runs in each filtered repo, with %f as the repo path, Enter to run, Esc to cancel",
+                    ),
+                    layout[1],
+                );
+                return;
+            }
+            Some(running) => running,
+        };
+
+        let layout =
+            Layout::vertical(vec![Constraint::Ratio(1, 2), Constraint::Ratio(1, 2)]).split(area);
+
+        // keep the selected repo in view
+        const BORDER_WASTAGE: usize = 2;
+        let view_height = (layout[0].height as usize).saturating_sub(BORDER_WASTAGE);
+        let skip = (running.selected + 1).saturating_sub(view_height);
+
+        let rows = running
+            .results
+            .iter()
+            .enumerate()
+            .skip(skip)
+            .map(|(i, (path, result))| {
+                let status = match result {
+                    Some(result) => {
+                        Cell::from(result.status.to_string()).style(if result.success() {
+                            Style::new()
+                        } else {
+                            Style::new().fg(Color::Red)
+                        })
+                    }
+                    None => Cell::from("…"),
+                };
+                Row::new([Cell::from(display_path(path)), status]).style(if i == running.selected {
+                    Style::new().add_modifier(Modifier::REVERSED)
+                } else {
+                    Style::new()
+                })
+            })
+            .collect::<Vec<_>>();
+        let n_done = running.results.values().filter(|r| r.is_some()).count();
+
+        frame.render_widget(
+            Table::new(rows, [Constraint::Fill(1), Constraint::Length(24)]).block(
+                Block::bordered()
+                    .title(format!(
+                        " {} {}/{}{} ",
+                        running.command,
+                        n_done,
+                        running.results.len(),
+                        if running.completed { "" } else { " running" }
+                    ))
+                    .title_alignment(Alignment::Center)
+                    .border_type(BorderType::Rounded),
+            ),
+            layout[0],
+        );
+
+        let output = match running.results.values().nth(running.selected) {
+            Some(Some(result)) => {
+                let mut text = Text::raw(result.stdout.as_str());
+                text.extend(Text::styled(
+                    result.stderr.as_str(),
+                    Style::new().fg(Color::Red),
+                ));
+                text
+            }
+            _ => Text::default(),
+        };
+        frame.render_widget(
+            Paragraph::new(output).wrap(Wrap { trim: false }).block(
+                Block::bordered()
+                    .title(" output, Esc to close ")
+                    .title_alignment(Alignment::Center)
+                    .border_type(BorderType::Rounded),
+            ),
+            layout[1],
+        );
+    }
+}
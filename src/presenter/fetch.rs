@@ -0,0 +1,185 @@
+use crossterm::event::{KeyCode, KeyEvent};
+use ratatui::{
+    layout::{Alignment, Constraint, Layout, Rect},
+    style::{Color, Modifier, Style},
+    text::Text,
+    widgets::{Block, BorderType, Cell, Paragraph, Row, Table, Wrap},
+    Frame,
+};
+use std::{
+    borrow::Cow,
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use crate::{model::RepoId, service::exec::ExecResult};
+
+/// Fetching all remotes of several local repos.
+#[derive(Debug)]
+pub struct Fetch {
+    states: BTreeMap<RepoId, FetchState>,
+    failures: Vec<ExecResult>,
+    /// selected failure
+    selected: usize,
+    completed: bool,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum FetchState {
+    Pending,
+    Fetching,
+    Fetched,
+    Failed,
+}
+
+pub enum Outcome {
+    Close,
+}
+
+impl Fetch {
+    pub fn new(paths: &[PathBuf]) -> Self {
+        Self {
+            states: paths
+                .iter()
+                .map(|path| (RepoId::local(path.clone()), FetchState::Pending))
+                .collect(),
+            failures: Vec::default(),
+            selected: 0,
+            completed: false,
+        }
+    }
+
+    pub fn is_running(&self) -> bool {
+        !self.completed
+    }
+
+    pub fn state(&self, id: &RepoId) -> Option<FetchState> {
+        self.states.get(id).copied()
+    }
+
+    pub fn has_failures(&self) -> bool {
+        !self.failures.is_empty()
+    }
+
+    pub fn fetching(&mut self, path: PathBuf) {
+        self.states
+            .insert(RepoId::local(path), FetchState::Fetching);
+    }
+
+    pub fn fetched(&mut self, result: ExecResult) {
+        if result.success() {
+            self.states
+                .insert(RepoId::local(result.path.clone()), FetchState::Fetched);
+        } else {
+            self.states
+                .insert(RepoId::local(result.path.clone()), FetchState::Failed);
+            self.failures.push(result);
+            self.failures.sort_by(|r0, r1| r0.path.cmp(&r1.path));
+        }
+    }
+
+    /// Mark as completed, returning a summary.
+    pub fn completed(&mut self) -> String {
+        self.completed = true;
+        format!(
+            "fetched {} repos, {} failed",
+            self.states.len(),
+            self.failures.len()
+        )
+    }
+
+    pub fn handle_key(&mut self, ev: KeyEvent) -> Option<Outcome> {
+        let last = self.failures.len().saturating_sub(1);
+        match ev.code {
+            KeyCode::Esc => return Some(Outcome::Close),
+            KeyCode::Up => self.selected = self.selected.saturating_sub(1),
+            KeyCode::Down => self.selected = (self.selected + 1).min(last),
+            _ => (),
+        }
+        None
+    }
+
+    /// Render the failures.
+    pub fn render<F>(&self, frame: &mut Frame, area: Rect, display_path: F)
+    where
+        F: Fn(&Path) -> Cow<'_, str>,
+    {
+        let layout =
+            Layout::vertical(vec![Constraint::Ratio(1, 2), Constraint::Ratio(1, 2)]).split(area);
+
+        const BORDER_WASTAGE: usize = 2;
+        let view_height = (layout[0].height as usize).saturating_sub(BORDER_WASTAGE);
+        let skip = (self.selected + 1).saturating_sub(view_height);
+
+        let rows = self
+            .failures
+            .iter()
+            .enumerate()
+            .skip(skip)
+            .map(|(i, result)| {
+                Row::new([
+                    Cell::from(display_path(&result.path)),
+                    Cell::from(result.status.to_string()).style(Style::new().fg(Color::Red)),
+                ])
+                .style(if i == self.selected {
+                    Style::new().add_modifier(Modifier::REVERSED)
+                } else {
+                    Style::new()
+                })
+            })
+            .collect::<Vec<_>>();
+
+        frame.render_widget(
+            Table::new(rows, [Constraint::Fill(1), Constraint::Length(24)]).block(
+                Block::bordered()
+                    .title(format!(
+                        " {} of {} fetches failed ",
+                        self.failures.len(),
+                        self.states.len()
+                    ))
+                    .title_alignment(Alignment::Center)
+                    .border_type(BorderType::Rounded),
+            ),
+            layout[0],
+        );
+
+        let output = match self.failures.get(self.selected) {
+            Some(result) => Text::raw(result.stderr.as_str()),
+            None => Text::default(),
+        };
+        frame.render_widget(
+            Paragraph::new(output).wrap(Wrap { trim: false }).block(
+                Block::bordered()
+                    .title(" error, Esc to close ")
+                    .title_alignment(Alignment::Center)
+                    .border_type(BorderType::Rounded),
+            ),
+            layout[1],
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::service::exec::ExecStatus;
+
+    #[test]
+    fn test_state_is_per_repo_id() {
+        let mut fetch = Fetch::new(&[PathBuf::from("/a")]);
+        fetch.fetched(ExecResult {
+            path: PathBuf::from("/a"),
+            status: ExecStatus::Exited(1),
+            stdout: String::new(),
+            stderr: "fatal: unable to access\n".to_string(),
+        });
+
+        let local = RepoId::local(PathBuf::from("/a"));
+        assert_eq!(fetch.state(&local), Some(FetchState::Failed));
+        let remote = RepoId {
+            host: Some("host".to_string()),
+            ..local
+        };
+        assert_eq!(fetch.state(&remote), None);
+    }
+}
@@ -0,0 +1,246 @@
+use crossterm::event::{KeyCode, KeyEvent};
+use ratatui::{
+    layout::{Alignment, Constraint, Layout, Rect},
+    style::{Color, Modifier, Style, Stylize},
+    widgets::{Block, BorderType, Cell, Paragraph, Row, Table},
+    Frame,
+};
+use std::{borrow::Cow, collections::BTreeSet};
+
+use super::clone::CloneState;
+use crate::model::{GitHubRepo, LocalRepo, Model, Remote, RepoId};
+
+/// GitHub repos not cloned here, and repos here whose GitHub remote no longer exists.
+#[derive(Default, Debug)]
+pub struct GitHub {
+    /// index into the repos of both sections, in order
+    selected: usize,
+    /// full names of repos marked for cloning
+    marked: BTreeSet<String>,
+}
+
+pub enum Outcome {
+    /// go to the repo in the main view
+    Select(RepoId),
+    /// list GitHub repos again
+    Refresh,
+    Clone(Vec<GitHubRepo>),
+    Close,
+}
+
+/// An entry in either section.
+enum Item<'a> {
+    NotCloned(&'a GitHubRepo),
+    Gone(&'a LocalRepo, Vec<&'a Remote>),
+}
+
+impl GitHub {
+    pub fn handle_key(&mut self, ev: KeyEvent, model: &Model) -> Option<Outcome> {
+        let items = items(model);
+        self.selected = self.selected.min(items.len().saturating_sub(1));
+
+        match ev.code {
+            KeyCode::Esc => return Some(Outcome::Close),
+            KeyCode::Char('r') => return Some(Outcome::Refresh),
+            KeyCode::Up => self.selected = self.selected.saturating_sub(1),
+            KeyCode::Down => self.selected = (self.selected + 1).min(items.len().saturating_sub(1)),
+            KeyCode::Enter => {
+                if let Some(Item::Gone(repo, _)) = items.get(self.selected) {
+                    return Some(Outcome::Select(repo.id()));
+                }
+            }
+            KeyCode::Char(' ') => {
+                if let Some(Item::NotCloned(github_repo)) = items.get(self.selected) {
+                    if !self.marked.remove(&github_repo.full_name) {
+                        self.marked.insert(github_repo.full_name.clone());
+                    }
+                    self.selected = (self.selected + 1).min(items.len().saturating_sub(1));
+                }
+            }
+            KeyCode::Char('c') => {
+                // those marked, or if none, the selected one
+                let to_clone = items
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(i, item)| match item {
+                        Item::NotCloned(github_repo)
+                            if self.marked.contains(&github_repo.full_name)
+                                || (self.marked.is_empty() && i == self.selected) =>
+                        {
+                            Some((*github_repo).clone())
+                        }
+                        _ => None,
+                    })
+                    .collect::<Vec<_>>();
+                self.marked.clear();
+                if !to_clone.is_empty() {
+                    return Some(Outcome::Clone(to_clone));
+                }
+            }
+            _ => (),
+        }
+        None
+    }
+
+    pub fn render<'a, F1, F2>(
+        &self,
+        frame: &mut Frame,
+        area: Rect,
+        model: &Model,
+        display_repo: F1,
+        clone_state: F2,
+    ) where
+        F1: Fn(&LocalRepo) -> Cow<'_, str>,
+        F2: Fn(&GitHubRepo) -> Option<&'a CloneState>,
+    {
+        let layout = Layout::vertical(vec![Constraint::Fill(1), Constraint::Length(1)]).split(area);
+        let block = Block::bordered()
+            .title(if model.github_listing_in_progress() {
+                " GitHub inventory, listing… "
+            } else {
+                " GitHub inventory "
+            })
+            .title_alignment(Alignment::Center)
+            .border_type(BorderType::Rounded);
+
+        if !model.github_listed() {
+            let text = if model.github_listing_in_progress() {
+                "listing GitHub repos…"
+            } else {
+                "GitHub repos not listed"
+            };
+            frame.render_widget(Paragraph::new(text).block(block), layout[0]);
+        } else {
+            let items = items(model);
+            let n_not_cloned = items
+                .iter()
+                .filter(|item| matches!(item, Item::NotCloned(_)))
+                .count();
+
+            let mut rows = vec![heading("on GitHub, not cloned here", n_not_cloned)];
+            let mut selected_row = 0;
+            for (i, item) in items.iter().enumerate() {
+                if i == n_not_cloned {
+                    rows.push(heading(
+                        "cloned here, gone from GitHub",
+                        items.len() - n_not_cloned,
+                    ));
+                }
+                if i == self.selected {
+                    selected_row = rows.len();
+                }
+                let row = match item {
+                    Item::NotCloned(github_repo) => Row::new([
+                        Cell::from(format!(
+                            "{} {}",
+                            if self.marked.contains(&github_repo.full_name) {
+                                "✓"
+                            } else {
+                                " "
+                            },
+                            github_repo.full_name
+                        )),
+                        Cell::from(flags(github_repo)).style(Style::new().fg(Color::Yellow)),
+                        match clone_state(github_repo) {
+                            Some(CloneState::Pending) => Cell::from("… clone pending"),
+                            Some(CloneState::Cloning(progress)) => {
+                                Cell::from(format!("⟳ {}", progress))
+                            }
+                            Some(CloneState::Failed(reason)) => Cell::from(format!("✗ {}", reason))
+                                .style(Style::new().fg(Color::Red)),
+                            None => Cell::from(
+                                github_repo
+                                    .pushed_at
+                                    .map(|pushed_at| {
+                                        format!("pushed {}", pushed_at.format("%Y-%m-%d"))
+                                    })
+                                    .unwrap_or_default(),
+                            ),
+                        },
+                    ]),
+                    Item::Gone(repo, remotes) => Row::new([
+                        Cell::from(format!("  {}", display_repo(repo))),
+                        Cell::from(
+                            remotes
+                                .iter()
+                                .map(|remote| remote.name())
+                                .collect::<Vec<_>>()
+                                .join(" "),
+                        ),
+                        Cell::from(
+                            remotes
+                                .iter()
+                                .filter_map(|remote| remote.identity())
+                                .map(|identity| identity.to_string())
+                                .collect::<Vec<_>>()
+                                .join(" "),
+                        ),
+                    ]),
+                };
+                rows.push(row.style(if i == self.selected {
+                    Style::new().add_modifier(Modifier::REVERSED)
+                } else {
+                    Style::new()
+                }));
+            }
+            // the second section still gets its heading when empty
+            if n_not_cloned == items.len() {
+                rows.push(heading("cloned here, gone from GitHub", 0));
+            }
+
+            // keep the selected repo in view
+            const BORDER_WASTAGE: usize = 2;
+            let view_height = (layout[0].height as usize).saturating_sub(BORDER_WASTAGE);
+            let skip = (selected_row + 1).saturating_sub(view_height);
+
+            frame.render_widget(
+                Table::new(
+                    rows.into_iter().skip(skip),
+                    [
+                        Constraint::Ratio(1, 2),
+                        Constraint::Ratio(1, 6),
+                        Constraint::Ratio(1, 3),
+                    ],
+                )
+                .block(block),
+                layout[0],
+            );
+        }
+
+        frame.render_widget(
+            Paragraph::new("Space mark  c clone  Enter go to repo  r list again  Esc back"),
+            layout[1],
+        );
+    }
+}
+
+fn items(model: &Model) -> Vec<Item<'_>> {
+    model
+        .not_cloned()
+        .into_iter()
+        .map(Item::NotCloned)
+        .chain(
+            model
+                .gone_from_github()
+                .into_iter()
+                .map(|(repo, remotes)| Item::Gone(repo, remotes)),
+        )
+        .collect()
+}
+
+fn heading(title: &str, n: usize) -> Row<'static> {
+    Row::new([Cell::from(format!("{}: {}", title, n)).bold()])
+}
+
+/// Properties worth knowing before cloning.
+fn flags(github_repo: &GitHubRepo) -> String {
+    [
+        (github_repo.fork, "fork"),
+        (github_repo.archived, "archived"),
+        (github_repo.private, "private"),
+    ]
+    .into_iter()
+    .filter_map(|(flag, name)| flag.then_some(name))
+    .collect::<Vec<_>>()
+    .join(" ")
+}
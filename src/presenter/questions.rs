@@ -0,0 +1,119 @@
+use crossterm::event::{Event, KeyCode, KeyEvent};
+use ratatui::{
+    layout::{Alignment, Constraint, Flex, Layout, Rect},
+    style::{Color, Modifier, Style},
+    text::{Line, Span, Text},
+    widgets::{Block, BorderType, Clear, Paragraph, Wrap},
+    Frame,
+};
+use std::collections::VecDeque;
+use tui_input::{backend::crossterm::EventHandler, Input};
+
+use crate::ssh::Question;
+
+/// Questions from services which only the user can answer, asked one at a time
+/// over whatever else is showing.
+#[derive(Default, Debug)]
+pub struct Questions {
+    pending: VecDeque<Question>,
+    /// answer to the current question, if it is a secret
+    input: Input,
+}
+
+impl Questions {
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    pub fn ask(&mut self, question: Question) {
+        self.pending.push_back(question);
+    }
+
+    /// Answer the current question, ignoring keys which aren't an answer.
+    pub fn handle_key(&mut self, ev: KeyEvent) {
+        // replies fail harmlessly if the connection attempt was abandoned meanwhile
+        match self.pending.front() {
+            Some(Question::TrustHostKey(_)) => {
+                let answer = match ev.code {
+                    KeyCode::Char('y') => true,
+                    KeyCode::Char('n') | KeyCode::Esc => false,
+                    _ => return,
+                };
+                if let Some(Question::TrustHostKey(unknown)) = self.pending.pop_front() {
+                    let _ = unknown.reply.send(answer);
+                }
+            }
+            Some(Question::Secret(_)) => {
+                let answer = match ev.code {
+                    KeyCode::Enter => Some(self.input.value().to_string()),
+                    KeyCode::Esc => None,
+                    _ => {
+                        self.input.handle_event(&Event::Key(ev));
+                        return;
+                    }
+                };
+                self.input.reset();
+                if let Some(Question::Secret(secret)) = self.pending.pop_front() {
+                    let _ = secret.reply.send(answer);
+                }
+            }
+            None => (),
+        }
+    }
+
+    pub fn render(&self, frame: &mut Frame, area: Rect) {
+        let Some(question) = self.pending.front() else {
+            return;
+        };
+
+        let (title, text) = match question {
+            Question::TrustHostKey(unknown) => (
+                " unknown host key ".to_string(),
+                Text::from(vec![
+                    Line::from(format!(
+                        "The authenticity of host {} can't be established.",
+                        unknown.host
+                    )),
+                    Line::from(format!(
+                        "{} key fingerprint is {}",
+                        unknown.key_type, unknown.fingerprint
+                    )),
+                    Line::default(),
+                    Line::from("Trust it and add it to known_hosts? y/n"),
+                ]),
+            ),
+            Question::Secret(secret) => {
+                let mut text = Text::from(secret.prompt.as_str());
+                // like ssh, show nothing of what's typed unless the server says otherwise
+                let answer = if secret.echo { self.input.value() } else { "" };
+                text.push_line(Line::from(vec![
+                    Span::styled(">", Style::new().add_modifier(Modifier::REVERSED)),
+                    Span::raw(" "),
+                    Span::raw(answer),
+                ]));
+                text.push_line(Line::default());
+                text.push_line(Line::from("Enter to answer, Esc to decline"));
+                (format!(" ssh {} ", secret.host), text)
+            }
+        };
+
+        let [area] = Layout::vertical([Constraint::Length(text.height() as u16 + 2)])
+            .flex(Flex::Center)
+            .areas(area);
+        let [area] = Layout::horizontal([Constraint::Percentage(80)])
+            .flex(Flex::Center)
+            .areas(area);
+
+        frame.render_widget(Clear, area);
+        frame.render_widget(
+            Paragraph::new(text).wrap(Wrap { trim: false }).block(
+                Block::bordered()
+                    .title(title)
+                    .title_alignment(Alignment::Center)
+                    .border_type(BorderType::Rounded)
+                    .border_style(Style::new().fg(Color::Yellow)),
+            ),
+            area,
+        );
+    }
+}
@@ -0,0 +1,249 @@
+use crossterm::event::{Event, KeyCode, KeyEvent};
+use ratatui::{
+    layout::{Alignment, Constraint, Layout, Rect},
+    style::{Color, Modifier, Style, Stylize},
+    text::{Line, Span},
+    widgets::{Block, BorderType, Cell, Paragraph, Row, Table},
+    Frame,
+};
+use std::path::{Path, PathBuf};
+use tui_input::{backend::crossterm::EventHandler, Input};
+
+use crate::model::{LocalRepo, RemoteEdit};
+
+/// Editing the remotes of a single repo.
+#[derive(Debug)]
+pub struct RemoteEditor {
+    path: PathBuf,
+    selected: usize,
+    prompt: Option<Prompt>,
+}
+
+pub enum Outcome {
+    Edit(RemoteEdit),
+    Close,
+}
+
+#[derive(Debug)]
+struct Prompt {
+    kind: PromptKind,
+    input: Input,
+}
+
+#[derive(Debug)]
+enum PromptKind {
+    AddName,
+    AddUrl { name: String },
+    Rename { name: String },
+    SetUrl { name: String },
+    SetPushUrl { name: String },
+    ConfirmRemove { name: String },
+}
+
+impl Prompt {
+    fn new(kind: PromptKind, value: &str) -> Self {
+        Self {
+            kind,
+            input: Input::new(value.to_string()),
+        }
+    }
+
+    fn label(&self) -> String {
+        use PromptKind::*;
+        match &self.kind {
+            AddName => "new remote name:".to_string(),
+            AddUrl { name } => format!("URL for {}:", name),
+            Rename { name } => format!("rename {} to:", name),
+            SetUrl { name } => format!("URL for {}:", name),
+            SetPushUrl { name } => format!("push URL for {} (empty for none):", name),
+            ConfirmRemove { name } => format!("remove {}? (y/n)", name),
+        }
+    }
+}
+
+impl RemoteEditor {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            selected: 0,
+            prompt: None,
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn handle_key(&mut self, ev: KeyEvent, repo: &LocalRepo) -> Option<Outcome> {
+        // remotes may have been removed since
+        self.selected = self.selected.min(repo.remotes.len().saturating_sub(1));
+
+        match self.prompt.take() {
+            Some(prompt) => self.handle_prompt_key(ev, prompt),
+            None => {
+                let selected = repo.remotes.get(self.selected);
+                match (ev.code, selected) {
+                    (KeyCode::Esc, _) => return Some(Outcome::Close),
+                    (KeyCode::Up, _) => self.selected = self.selected.saturating_sub(1),
+                    (KeyCode::Down, _) => {
+                        self.selected =
+                            (self.selected + 1).min(repo.remotes.len().saturating_sub(1))
+                    }
+                    (KeyCode::Char('a'), _) => {
+                        self.prompt = Some(Prompt::new(PromptKind::AddName, ""))
+                    }
+                    (KeyCode::Char('r'), Some(remote)) => {
+                        let name = remote.name().to_string();
+                        self.prompt = Some(Prompt::new(PromptKind::Rename { name }, remote.name()))
+                    }
+                    (KeyCode::Char('d'), Some(remote)) => {
+                        let name = remote.name().to_string();
+                        self.prompt = Some(Prompt::new(PromptKind::ConfirmRemove { name }, ""))
+                    }
+                    (KeyCode::Char('u'), Some(remote)) => {
+                        let name = remote.name().to_string();
+                        let url = remote.configured_url().or(remote.fetch_url());
+                        self.prompt = Some(Prompt::new(
+                            PromptKind::SetUrl { name },
+                            url.unwrap_or_default(),
+                        ))
+                    }
+                    (KeyCode::Char('p'), Some(remote)) => {
+                        let name = remote.name().to_string();
+                        // only a configured one, lest Enter write the fetch URL as push URL
+                        self.prompt = Some(Prompt::new(
+                            PromptKind::SetPushUrl { name },
+                            remote.configured_push_url().unwrap_or_default(),
+                        ))
+                    }
+                    _ => (),
+                }
+                None
+            }
+        }
+    }
+
+    fn handle_prompt_key(&mut self, ev: KeyEvent, mut prompt: Prompt) -> Option<Outcome> {
+        use PromptKind::*;
+
+        if let ConfirmRemove { name } = prompt.kind {
+            return (ev.code == KeyCode::Char('y'))
+                .then_some(Outcome::Edit(RemoteEdit::Remove { name }));
+        }
+
+        match ev.code {
+            KeyCode::Esc => None,
+            KeyCode::Enter => {
+                let value = prompt.input.value().trim().to_string();
+                if value.is_empty() && !matches!(prompt.kind, SetPushUrl { .. }) {
+                    self.prompt = Some(prompt);
+                    return None;
+                }
+                match prompt.kind {
+                    AddName => {
+                        self.prompt = Some(Prompt::new(AddUrl { name: value }, ""));
+                        None
+                    }
+                    AddUrl { name } => Some(Outcome::Edit(RemoteEdit::Add { name, url: value })),
+                    Rename { name } => Some(Outcome::Edit(RemoteEdit::Rename {
+                        name,
+                        new_name: value,
+                    })),
+                    SetUrl { name } => Some(Outcome::Edit(RemoteEdit::SetUrl { name, url: value })),
+                    SetPushUrl { name } => Some(Outcome::Edit(RemoteEdit::SetPushUrl {
+                        name,
+                        url: (!value.is_empty()).then_some(value),
+                    })),
+                    ConfirmRemove { .. } => unreachable!(),
+                }
+            }
+            _ => {
+                prompt.input.handle_event(&Event::Key(ev));
+                self.prompt = Some(prompt);
+                None
+            }
+        }
+    }
+
+    pub fn render(&self, frame: &mut Frame, area: Rect, repo: &LocalRepo, display_path: &str) {
+        let layout = Layout::vertical(vec![
+            Constraint::Length(1),
+            Constraint::Fill(1),
+            Constraint::Length(1),
+        ])
+        .split(area);
+
+        if let Some(prompt) = self.prompt.as_ref() {
+            frame.render_widget(
+                Paragraph::new(Line::from(vec![
+                    Span::styled(
+                        prompt.label(),
+                        Style::new().add_modifier(Modifier::REVERSED),
+                    ),
+                    Span::raw(" "),
+                    Span::raw(prompt.input.value()),
+                ])),
+                layout[0],
+            );
+        }
+
+        let rows = repo
+            .remotes
+            .iter()
+            .enumerate()
+            .map(|(i, remote)| {
+                let push_url = if remote.push_differs() {
+                    Cell::from(url_display(remote.push_url(), remote.configured_push_url()))
+                        .style(Style::new().fg(Color::Yellow))
+                } else {
+                    Cell::from("same as fetch")
+                };
+                Row::new([
+                    Cell::from(remote.name()),
+                    Cell::from(url_display(remote.fetch_url(), remote.configured_url())),
+                    push_url,
+                ])
+                .style(if i == self.selected {
+                    Style::new().add_modifier(Modifier::REVERSED)
+                } else {
+                    Style::new()
+                })
+            })
+            .collect::<Vec<_>>();
+
+        frame.render_widget(
+            Table::new(
+                rows,
+                [
+                    Constraint::Length(16),
+                    Constraint::Fill(1),
+                    Constraint::Fill(1),
+                ],
+            )
+            .header(Row::new(["name", "fetch URL", "push URL"]).style(Style::new().bold()))
+            .block(
+                Block::bordered()
+                    .title(format!(" remotes of {} ", display_path))
+                    .title_alignment(Alignment::Center)
+                    .border_type(BorderType::Rounded),
+            ),
+            layout[1],
+        );
+
+        frame.render_widget(
+            Paragraph::new("a add  r rename  d remove  u set URL  p set push URL  Esc back"),
+            layout[2],
+        );
+    }
+}
+
+/// The effective URL, along with what was configured if rewritten by `insteadOf`.
+fn url_display(effective: Option<&str>, configured: Option<&str>) -> String {
+    match (effective, configured) {
+        (Some(effective), Some(configured)) if effective != configured => {
+            format!("{} (configured {})", effective, configured)
+        }
+        (Some(effective), _) => effective.to_string(),
+        (None, _) => "none".to_string(),
+    }
+}
@@ -0,0 +1,172 @@
+use crossterm::event::{Event, KeyCode, KeyEvent};
+use ratatui::{
+    layout::{Alignment, Constraint, Layout, Rect},
+    style::{Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, BorderType, Paragraph, Row, Table},
+    Frame,
+};
+use std::{borrow::Cow, collections::BTreeSet, path::Path};
+use tui_input::{backend::crossterm::EventHandler, Input};
+
+use crate::model::{LocalRepo, Model, Remote, UrlRewrite};
+
+/// Rewriting of remote URLs across all repos, previewing what would change.
+#[derive(Default, Debug)]
+pub struct RewriteUrls {
+    pattern_input: Input,
+    replacement_input: Input,
+    replacement_focused: bool,
+}
+
+pub enum Outcome {
+    Apply(UrlRewrite),
+    Cancel,
+}
+
+/// A configured remote URL as it would be rewritten.
+pub struct Rewritten<'a> {
+    pub repo: &'a LocalRepo,
+    pub remote: &'a Remote,
+    /// `url` or `pushurl`
+    pub key: &'static str,
+    pub configured: &'a str,
+    pub url: String,
+}
+
+impl RewriteUrls {
+    pub fn handle_key(&mut self, ev: KeyEvent) -> Option<Outcome> {
+        match ev.code {
+            KeyCode::Esc => Some(Outcome::Cancel),
+            KeyCode::Enter => Some(Outcome::Apply(self.rewrite())),
+            KeyCode::Tab | KeyCode::BackTab | KeyCode::Up | KeyCode::Down => {
+                self.replacement_focused = !self.replacement_focused;
+                None
+            }
+            _ => {
+                let input = if self.replacement_focused {
+                    &mut self.replacement_input
+                } else {
+                    &mut self.pattern_input
+                };
+                input.handle_event(&Event::Key(ev));
+                None
+            }
+        }
+    }
+
+    pub fn rewrite(&self) -> UrlRewrite {
+        UrlRewrite::new(self.pattern_input.value(), self.replacement_input.value())
+    }
+
+    /// Every configured remote URL in the model which would be changed by the rewrite.
+    pub fn preview<'a>(&self, model: &'a Model) -> Vec<Rewritten<'a>> {
+        let rewrite = self.rewrite();
+        let mut preview = Vec::new();
+        // remote editing is only possible locally
+        for repo in model
+            .repos
+            .values()
+            .filter(|repo| repo.is_local() && !repo.vanished)
+        {
+            for remote in repo.remotes.iter() {
+                for (key, configured) in [
+                    ("url", remote.configured_url()),
+                    ("pushurl", remote.configured_push_url()),
+                ] {
+                    if let Some(configured) = configured {
+                        if let Some(url) = rewrite.apply(configured) {
+                            preview.push(Rewritten {
+                                repo,
+                                remote,
+                                key,
+                                configured,
+                                url,
+                            });
+                        }
+                    }
+                }
+            }
+        }
+        preview
+    }
+
+    pub fn render<F>(&self, frame: &mut Frame, area: Rect, preview: &[Rewritten], display_path: F)
+    where
+        F: Fn(&Path) -> Cow<'_, str>,
+    {
+        let layout = Layout::vertical(vec![
+            Constraint::Length(1),
+            Constraint::Length(1),
+            Constraint::Fill(1),
+        ])
+        .split(area);
+
+        for (i, (label, input, focused)) in [
+            ("pattern", &self.pattern_input, !self.replacement_focused),
+            (
+                "replacement",
+                &self.replacement_input,
+                self.replacement_focused,
+            ),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            let label_style = if focused {
+                Style::new().add_modifier(Modifier::REVERSED)
+            } else {
+                Style::new()
+            };
+            frame.render_widget(
+                Paragraph::new(Line::from(vec![
+                    Span::styled(format!("{:>11}", label), label_style),
+                    Span::raw(" "),
+                    Span::raw(input.value()),
+                ])),
+                layout[i],
+            );
+        }
+
+        let n_repos = preview
+            .iter()
+            .map(|rewritten| &rewritten.repo.path)
+            .collect::<BTreeSet<_>>()
+            .len();
+        let rows = preview
+            .iter()
+            .take(layout[2].height as usize)
+            .map(|rewritten| {
+                Row::new([
+                    display_path(&rewritten.repo.path),
+                    Cow::Owned(format!("{}.{}", rewritten.remote.name(), rewritten.key)),
+                    Cow::Borrowed(rewritten.configured),
+                    Cow::Owned(format!("→ {}", rewritten.url)),
+                ])
+            })
+            .collect::<Vec<_>>();
+
+        frame.render_widget(
+            Table::new(
+                rows,
+                [
+                    Constraint::Ratio(1, 4),
+                    Constraint::Length(16),
+                    Constraint::Ratio(3, 8),
+                    Constraint::Ratio(3, 8),
+                ],
+            )
+            .block(
+                Block::bordered()
+                    .title(format!(
+                        " rewrite {} remote URLs in {} repos, Enter to apply, Esc to cancel ",
+                        preview.len(),
+                        n_repos
+                    ))
+                    .title_alignment(Alignment::Center)
+                    .border_type(BorderType::Rounded),
+            ),
+            layout[2],
+        );
+    }
+}
@@ -1,2 +1,12 @@
+pub mod exec;
 pub mod filesystem;
+pub mod github;
+pub mod ssh;
 pub mod terminal;
+
+/// Receiver of service outcomes which are for the user rather than the model.
+pub trait Notify {
+    fn info(&mut self, message: String);
+
+    fn error(&mut self, message: String);
+}
@@ -0,0 +1,546 @@
+use futures::{stream, StreamExt};
+use std::{
+    fmt::{self, Display},
+    future::Future,
+    path::{Path, PathBuf},
+    process::Stdio,
+};
+use tokio::{io::AsyncReadExt, process::Command, sync::mpsc};
+use tracing::{debug, trace, warn};
+
+use crate::{
+    config::ExecConfig,
+    model::LocalRepo,
+    service::{filesystem, Notify},
+    template,
+};
+
+/// A command to run in each of several repos, with `%f` in args substituted by the repo path.
+#[derive(Clone, Debug)]
+pub struct ExecCommand {
+    pub command: String,
+    pub args: Vec<String>,
+    /// extra environment variables
+    pub env: Vec<(String, String)>,
+}
+
+impl ExecCommand {
+    /// Split a command line into words as a POSIX shell would, honouring quotes and backslashes,
+    /// though without expanding anything, or `None` if there are no words.
+    pub fn parse(command_line: &str) -> Result<Option<Self>, shell_words::ParseError> {
+        let mut words = shell_words::split(command_line)?.into_iter();
+        Ok(words.next().map(|command| Self {
+            command,
+            args: words.collect(),
+            env: Vec::default(),
+        }))
+    }
+
+    /// Fetch all remotes, failing rather than prompting for credentials.
+    fn fetch_all() -> Self {
+        Self {
+            command: "git".to_string(),
+            args: vec!["fetch".to_string(), "--all".to_string()],
+            env: non_interactive_git_env(),
+        }
+    }
+
+    /// Clone into `path`, reporting progress even though stderr isn't a terminal,
+    /// and failing rather than prompting for credentials.
+    fn git_clone(url: &str, path: &Path) -> Self {
+        Self {
+            command: "git".to_string(),
+            args: vec![
+                "clone".to_string(),
+                "--progress".to_string(),
+                url.to_string(),
+                path.to_string_lossy().into_owned(),
+            ],
+            env: non_interactive_git_env(),
+        }
+    }
+
+    pub fn format_args<S>(&self, f: S) -> Result<Vec<String>, template::Error>
+    where
+        S: AsRef<str>,
+    {
+        template::format_path_args(&self.args, f.as_ref())
+    }
+}
+
+/// Environment for git never to prompt, since there's no terminal for it.
+fn non_interactive_git_env() -> Vec<(String, String)> {
+    let mut env = vec![("GIT_TERMINAL_PROMPT".to_string(), "0".to_string())];
+    if std::env::var_os("GIT_SSH_COMMAND").is_none() {
+        env.push((
+            "GIT_SSH_COMMAND".to_string(),
+            "ssh -o BatchMode=yes".to_string(),
+        ));
+    }
+    env
+}
+
+/// A repo to clone, and where.
+#[derive(Clone, Debug)]
+pub struct CloneJob {
+    pub url: String,
+    pub path: PathBuf,
+}
+
+impl Display for ExecCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.command)?;
+        for arg in self.args.iter() {
+            write!(f, " {}", arg)?;
+        }
+        Ok(())
+    }
+}
+
+pub enum Request {
+    Run(ExecCommand, Vec<PathBuf>),
+    /// fetch all remotes of each repo
+    Fetch(Vec<PathBuf>),
+    CloneRepos(Vec<CloneJob>),
+}
+
+pub enum Event {
+    Result(ExecResult),
+    Completed,
+    Fetching(PathBuf),
+    /// the fetch result, and the repo as refreshed afterwards
+    Fetched(ExecResult, LocalRepo),
+    FetchCompleted,
+    Cloning(PathBuf),
+    /// the most recent progress report from git
+    CloneProgress(PathBuf, String),
+    /// the clone result, and if successful, the new repo
+    Cloned(ExecResult, Option<LocalRepo>),
+    CloneCompleted,
+}
+
+/// Outcome of running a command in a single repo.
+#[derive(Debug)]
+pub struct ExecResult {
+    pub path: PathBuf,
+    pub status: ExecStatus,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+#[derive(Debug)]
+pub enum ExecStatus {
+    Exited(i32),
+    /// terminated by a signal
+    Killed,
+    Failed(String),
+}
+
+impl ExecResult {
+    pub fn success(&self) -> bool {
+        matches!(self.status, ExecStatus::Exited(0))
+    }
+}
+
+impl Display for ExecStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use ExecStatus::*;
+        match self {
+            Exited(code) => write!(f, "exit {}", code),
+            Killed => f.write_str("killed"),
+            Failed(e) => write!(f, "failed: {}", e),
+        }
+    }
+}
+
+pub trait UpdateExec {
+    fn exec_result(&mut self, result: ExecResult);
+
+    fn exec_completed(&mut self);
+}
+
+/// Receiver of the progress of fetching repos.
+pub trait UpdateFetch {
+    fn fetching(&mut self, path: PathBuf);
+
+    fn fetched(&mut self, result: ExecResult, repo: LocalRepo);
+
+    fn fetch_completed(&mut self);
+}
+
+/// Receiver of the progress of cloning repos.
+pub trait UpdateClone {
+    fn cloning(&mut self, path: PathBuf);
+
+    fn clone_progress(&mut self, path: PathBuf, progress: String);
+
+    fn cloned(&mut self, result: ExecResult, repo: Option<LocalRepo>);
+
+    fn clone_completed(&mut self);
+}
+
+pub struct Service {
+    event_rx: mpsc::Receiver<Event>,
+    request_tx: mpsc::UnboundedSender<Request>,
+}
+
+impl Service {
+    pub fn new(config: &ExecConfig) -> Self {
+        let (event_tx, event_rx) = mpsc::channel(1);
+        let (request_tx, request_rx) = mpsc::unbounded_channel();
+
+        tokio::spawn(worker(config.clone(), request_rx, event_tx));
+
+        Self {
+            event_rx,
+            request_tx,
+        }
+    }
+
+    pub fn requester(&self) -> mpsc::UnboundedSender<Request> {
+        self.request_tx.clone()
+    }
+
+    pub fn recv_event(&mut self) -> impl Future<Output = Option<Event>> + '_ {
+        self.event_rx.recv()
+    }
+
+    pub async fn handle<M>(&mut self, ev: Event, model: &mut M)
+    where
+        M: UpdateExec + UpdateFetch + UpdateClone + Notify,
+    {
+        match ev {
+            Event::Fetching(path) => model.fetching(path),
+            Event::Fetched(result, repo) => model.fetched(result, repo),
+            Event::FetchCompleted => model.fetch_completed(),
+            Event::Cloning(path) => model.cloning(path),
+            Event::CloneProgress(path, progress) => model.clone_progress(path, progress),
+            Event::Cloned(result, repo) => model.cloned(result, repo),
+            Event::CloneCompleted => model.clone_completed(),
+            ev => self.handle_runs(ev, model).await,
+        }
+    }
+
+    /// Handle events for a model which only runs commands, never fetching or cloning.
+    pub async fn handle_runs<M>(&mut self, ev: Event, model: &mut M)
+    where
+        M: UpdateExec + Notify,
+    {
+        match ev {
+            Event::Result(result) => model.exec_result(result),
+            Event::Completed => model.exec_completed(),
+            // only sent in response to fetch and clone requests
+            _ => (),
+        }
+    }
+}
+
+async fn worker(
+    config: ExecConfig,
+    mut request_rx: mpsc::UnboundedReceiver<Request>,
+    event_tx: mpsc::Sender<Event>,
+) {
+    while let Some(request) = request_rx.recv().await {
+        use Request::*;
+
+        // each request runs alongside any others, with its own concurrency limit
+        match request {
+            Run(command, paths) => {
+                tokio::spawn(run_all(
+                    command,
+                    paths,
+                    config.concurrency,
+                    event_tx.clone(),
+                ));
+            }
+
+            Fetch(paths) => {
+                tokio::spawn(fetch_all(paths, config.concurrency, event_tx.clone()));
+            }
+
+            CloneRepos(jobs) => {
+                tokio::spawn(clone_all(jobs, config.concurrency, event_tx.clone()));
+            }
+        }
+    }
+}
+
+async fn run_all(
+    command: ExecCommand,
+    paths: Vec<PathBuf>,
+    concurrency: usize,
+    event_tx: mpsc::Sender<Event>,
+) {
+    trace!("running {} in {} repos", &command, paths.len());
+
+    let mut results = stream::iter(paths)
+        .map(|path| run(&command, path))
+        .buffer_unordered(concurrency);
+    while let Some(result) = results.next().await {
+        event_tx.send(Event::Result(result)).await.unwrap();
+    }
+
+    event_tx.send(Event::Completed).await.unwrap();
+}
+
+async fn fetch_all(paths: Vec<PathBuf>, concurrency: usize, event_tx: mpsc::Sender<Event>) {
+    trace!("fetching {} repos", paths.len());
+
+    let command = ExecCommand::fetch_all();
+    let mut fetched = stream::iter(paths)
+        .map(|path| {
+            let event_tx = event_tx.clone();
+            let command = &command;
+            async move {
+                event_tx.send(Event::Fetching(path.clone())).await.unwrap();
+                let result = run(command, path.clone()).await;
+                let repo = filesystem::local_repo(path).await;
+                event_tx.send(Event::Fetched(result, repo)).await.unwrap();
+            }
+        })
+        .buffer_unordered(concurrency);
+    while fetched.next().await.is_some() {}
+
+    event_tx.send(Event::FetchCompleted).await.unwrap();
+}
+
+async fn clone_all(jobs: Vec<CloneJob>, concurrency: usize, event_tx: mpsc::Sender<Event>) {
+    trace!("cloning {} repos", jobs.len());
+
+    let mut cloned = stream::iter(jobs)
+        .map(|job| {
+            let event_tx = event_tx.clone();
+            async move {
+                event_tx
+                    .send(Event::Cloning(job.path.clone()))
+                    .await
+                    .unwrap();
+                let result = git_clone(&job, &event_tx).await;
+                let repo = if result.success() {
+                    Some(filesystem::local_repo(job.path).await)
+                } else {
+                    None
+                };
+                event_tx.send(Event::Cloned(result, repo)).await.unwrap();
+            }
+        })
+        .buffer_unordered(concurrency);
+    while cloned.next().await.is_some() {}
+
+    event_tx.send(Event::CloneCompleted).await.unwrap();
+}
+
+/// Clone into a new directory, passing on git's progress reports as they come.
+async fn git_clone(job: &CloneJob, event_tx: &mpsc::Sender<Event>) -> ExecResult {
+    let path = &job.path;
+    let failed = |e: String, stderr: String| ExecResult {
+        path: path.clone(),
+        status: ExecStatus::Failed(e),
+        stdout: String::new(),
+        stderr,
+    };
+
+    // git would clone into an empty directory, but anything there suggests a mistake
+    if path.exists() {
+        return failed("already exists".to_string(), String::new());
+    }
+    if let Some(parent) = path.parent() {
+        if let Err(e) = tokio::fs::create_dir_all(parent).await {
+            return failed(e.to_string(), String::new());
+        }
+    }
+
+    let command = ExecCommand::git_clone(&job.url, path);
+    let mut child = match Command::new(&command.command)
+        .args(&command.args)
+        .envs(command.env.iter().map(|(k, v)| (k, v)))
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+    {
+        Ok(child) => child,
+        Err(e) => {
+            warn!("failed to run {}: {}", &command, e);
+            return failed(e.to_string(), String::new());
+        }
+    };
+
+    let mut progress = Progress::default();
+    if let Some(mut child_stderr) = child.stderr.take() {
+        let mut buf = [0; 4096];
+        let mut reported = String::new();
+        loop {
+            match child_stderr.read(&mut buf).await {
+                Ok(0) => break,
+                Ok(n) => {
+                    if let Some(latest) = progress.push(&buf[..n]) {
+                        if latest != reported {
+                            reported = latest;
+                            event_tx
+                                .send(Event::CloneProgress(path.clone(), reported.clone()))
+                                .await
+                                .unwrap();
+                        }
+                    }
+                }
+                Err(e) => {
+                    debug!("failed to read stderr of {}: {}", &command, e);
+                    break;
+                }
+            }
+        }
+    }
+    let stderr = String::from_utf8_lossy(&progress.stderr).into_owned();
+
+    match child.wait().await {
+        Ok(status) => ExecResult {
+            path: path.clone(),
+            status: match status.code() {
+                Some(code) => ExecStatus::Exited(code),
+                None => ExecStatus::Killed,
+            },
+            stdout: String::new(),
+            stderr,
+        },
+        Err(e) => failed(e.to_string(), stderr),
+    }
+}
+
+/// Stderr of git as read so far, in chunks.
+#[derive(Default)]
+struct Progress {
+    stderr: Vec<u8>,
+    /// where the line not yet ended starts, so earlier output isn't scanned again
+    line_start: usize,
+}
+
+impl Progress {
+    /// Add a chunk, returning the latest progress if any lines were ended by it.
+    fn push(&mut self, chunk: &[u8]) -> Option<String> {
+        self.stderr.extend_from_slice(chunk);
+        let end = self.line_start
+            + self.stderr[self.line_start..]
+                .iter()
+                .rposition(|b| matches!(b, b'\r' | b'\n'))?
+            + 1;
+        let ended = String::from_utf8_lossy(&self.stderr[self.line_start..end]);
+        let latest = latest_progress(&ended).map(str::to_string);
+        self.line_start = end;
+        latest
+    }
+}
+
+/// The last complete line, git rewriting its progress line by ending each update with a carriage return.
+fn latest_progress(stderr: &str) -> Option<&str> {
+    stderr
+        .rsplit(['\r', '\n'])
+        .skip(1)
+        .map(str::trim)
+        .find(|line| !line.is_empty())
+}
+
+async fn run(command: &ExecCommand, path: PathBuf) -> ExecResult {
+    let result = |status, stdout, stderr| ExecResult {
+        path: path.clone(),
+        status,
+        stdout,
+        stderr,
+    };
+
+    let args = match command.format_args(path.to_string_lossy()) {
+        Ok(args) => args,
+        Err(e) => {
+            return result(
+                ExecStatus::Failed(e.to_string()),
+                String::new(),
+                String::new(),
+            )
+        }
+    };
+
+    match Command::new(&command.command)
+        .args(args)
+        .current_dir(&path)
+        .envs(command.env.iter().map(|(k, v)| (k, v)))
+        .stdin(Stdio::null())
+        .kill_on_drop(true)
+        .output()
+        .await
+    {
+        Ok(output) => result(
+            match output.status.code() {
+                Some(code) => ExecStatus::Exited(code),
+                None => ExecStatus::Killed,
+            },
+            String::from_utf8_lossy(&output.stdout).into_owned(),
+            String::from_utf8_lossy(&output.stderr).into_owned(),
+        ),
+        Err(e) => {
+            warn!("failed to run {} in {:?}: {}", &command.command, &path, e);
+            result(
+                ExecStatus::Failed(e.to_string()),
+                String::new(),
+                String::new(),
+            )
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+
+    #[test_case("git status", Some(("git", vec!["status"])); "plain")]
+    #[test_case("  git   log  -1 ", Some(("git", vec!["log", "-1"])); "extra whitespace")]
+    #[test_case(r#"git commit -m "fix typo" --author='A U Thor <a@example.com>'"#, Some(("git", vec!["commit", "-m", "fix typo", "--author=A U Thor <a@example.com>"])); "quoted")]
+    #[test_case(r"grep -r a\ b '%f'", Some(("grep", vec!["-r", "a b", "%f"])); "escaped")]
+    #[test_case("  ", None; "empty")]
+    fn test_parse(command_line: &str, expected: Option<(&str, Vec<&str>)>) {
+        let command = ExecCommand::parse(command_line).unwrap();
+        assert_eq!(
+            command.as_ref().map(|command| (
+                command.command.as_str(),
+                command.args.iter().map(String::as_str).collect()
+            )),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_parse_unterminated_quote() {
+        assert!(ExecCommand::parse("git commit -m 'oops").is_err());
+    }
+
+    #[test_case("Cloning into 'r'...\n", Some("Cloning into 'r'..."); "line")]
+    #[test_case("Cloning into 'r'...\nReceiving objects:  45% (9/20)\r", Some("Receiving objects:  45% (9/20)"); "progress")]
+    #[test_case("Receiving objects:  45% (9/20)\rReceiving obj", Some("Receiving objects:  45% (9/20)"); "partial update")]
+    #[test_case("Cloning", None; "incomplete")]
+    fn test_latest_progress(stderr: &str, expected: Option<&str>) {
+        assert_eq!(latest_progress(stderr), expected);
+    }
+
+    #[test]
+    fn test_progress_in_chunks() {
+        let mut progress = Progress::default();
+        assert_eq!(progress.push(b"Cloning into 'r'"), None);
+        assert_eq!(
+            progress.push(b"...\nReceiving objects:  45%"),
+            Some("Cloning into 'r'...".to_string())
+        );
+        // with a character split between chunks
+        assert_eq!(progress.push(b" (9/20) \xe2\x9c"), None);
+        assert_eq!(
+            progress.push(b"\x93\r\n"),
+            Some("Receiving objects:  45% (9/20) \u{2713}".to_string())
+        );
+        assert_eq!(progress.push(b"done"), None);
+        assert_eq!(
+            String::from_utf8_lossy(&progress.stderr),
+            "Cloning into 'r'...\nReceiving objects:  45% (9/20) \u{2713}\r\ndone"
+        );
+    }
+}
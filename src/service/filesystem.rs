@@ -1,42 +1,61 @@
 use anyhow::{Context, Result};
 use futures::StreamExt;
 use std::{
-    borrow::Borrow,
-    collections::VecDeque,
+    collections::{BTreeSet, VecDeque},
     fmt::Debug,
     future::Future,
     path::{Path, PathBuf},
 };
 use tokio::{
     fs::{read_dir, symlink_metadata},
+    select,
     sync::mpsc,
     task::spawn_blocking,
+    time::{sleep_until, Duration, Instant},
 };
 use tokio_stream::wrappers::ReadDirStream;
 use tracing::{error, trace, warn};
 
 use crate::{
     config::FilesystemConfig,
-    model::{LocalRepo, Remote},
+    git,
+    model::{LocalRepo, RemoteEdit, RepoStatus, UpdateModel, UrlRewrite},
+    service::Notify,
 };
 
+mod watch;
+use watch::{is_lock_file, repo_containing, Change, Watches};
+
+/// How long to collect changes to repos before refreshing them, so that a rebase,
+/// for example, doesn't flood the event channel.
+const REFRESH_DELAY: Duration = Duration::from_millis(500);
+
 pub enum Request {
     Scan,
+    RewriteRemoteUrls(UrlRewrite, Vec<PathBuf>),
+    EditRemote(PathBuf, RemoteEdit),
 }
 
 pub enum Event {
+    ScanStarted,
     LocalRepo(LocalRepo),
+    ScanCompleted,
+    /// the path is gone, along with any repos at or below it
+    Removed(PathBuf),
+    Info(String),
+    Error(String),
 }
 
 pub struct Service {
     event_rx: mpsc::Receiver<Event>,
-    request_tx: mpsc::Sender<Request>,
+    request_tx: mpsc::UnboundedSender<Request>,
 }
 
 impl Service {
     pub fn new(config: &FilesystemConfig) -> Self {
         let (event_tx, event_rx) = mpsc::channel(1);
-        let (request_tx, request_rx) = mpsc::channel(1);
+        // unbounded so that requests never block the UI while a scan is in progress
+        let (request_tx, request_rx) = mpsc::unbounded_channel();
 
         tokio::spawn(worker(config.clone(), request_rx, event_tx));
 
@@ -46,7 +65,7 @@ impl Service {
         }
     }
 
-    pub fn requester(&self) -> mpsc::Sender<Request> {
+    pub fn requester(&self) -> mpsc::UnboundedSender<Request> {
         self.request_tx.clone()
     }
 
@@ -54,61 +73,253 @@ impl Service {
         self.event_rx.recv()
     }
 
-    pub async fn handle<F>(&mut self, ev: Event, add_local_repo: F)
+    pub async fn handle<M>(&mut self, ev: Event, model: &mut M)
     where
-        F: FnOnce(LocalRepo),
+        M: UpdateModel + Notify,
     {
         match ev {
-            Event::LocalRepo(repo) => add_local_repo(repo),
+            Event::ScanStarted => model.scan_started(None),
+            Event::LocalRepo(repo) => model.add_local_repo(repo),
+            Event::ScanCompleted => model.scan_completed(None),
+            Event::Removed(path) => model.remove_local_repos(&path),
+            Event::Info(message) => model.info(message),
+            Event::Error(message) => model.error(message),
         }
     }
 }
 
 async fn worker(
     config: FilesystemConfig,
-    mut request_rx: mpsc::Receiver<Request>,
+    mut request_rx: mpsc::UnboundedReceiver<Request>,
     event_tx: mpsc::Sender<Event>,
 ) {
-    while let Some(request) = request_rx.recv().await {
-        use Request::*;
-
-        match request {
-            Scan => {
-                let mut pending_dirs = config
-                    .scanner
-                    .roots
-                    .iter()
-                    .map(|rootdir| PathBuf::from(shellexpand::tilde(&rootdir).into_owned()))
-                    .collect::<VecDeque<_>>();
-
-                while let Some(dir) = pending_dirs.pop_front() {
-                    let excluded = config.scanner.excludes.is_match(&dir);
-                    trace!("considering {:?}", &dir);
-                    if !excluded {
-                        trace!("included {:?}", &dir);
-                        let git_dir = dir.join(".git");
-
-                        if is_primary_git_worktree(&git_dir).await {
-                            let remotes = git_remotes(git_dir).await;
-                            let repo = LocalRepo::new(dir, remotes);
+    let (change_tx, mut change_rx) = mpsc::unbounded_channel();
+    let mut watches = Watches::new(change_tx);
+    let mut changed_repos = BTreeSet::<PathBuf>::new();
+    let mut refresh_at: Option<Instant> = None;
+
+    loop {
+        select! {
+            request = request_rx.recv() => match request {
+                Some(request) => handle_request(request, &config, &mut watches, &event_tx).await,
+                None => break,
+            },
+            Some(change) = change_rx.recv() => match repo_containing(change.path()) {
+                Some(repo_dir) => {
+                    if !is_lock_file(change.path()) {
+                        changed_repos.insert(repo_dir.to_path_buf());
+                        refresh_at.get_or_insert_with(|| Instant::now() + REFRESH_DELAY);
+                    }
+                }
+                None => handle_change(change, &config, &mut watches, &event_tx).await,
+            },
+            _ = sleep_until(refresh_at.unwrap_or_else(Instant::now)), if refresh_at.is_some() => {
+                refresh_at = None;
+                for repo_dir in std::mem::take(&mut changed_repos) {
+                    trace!("refreshing {:?}", &repo_dir);
+                    // it may since have been removed altogether
+                    if is_primary_git_worktree(repo_dir.join(".git")).await {
+                        if watches.is_watched(&repo_dir) {
+                            // newly a repo, its git dir having been filled in since it appeared
+                            watches.unwatch_all(&repo_dir);
+                            watches.watch_repo(&repo_dir);
+                        }
+                        let repo = local_repo(repo_dir).await;
+                        event_tx.send(Event::LocalRepo(repo)).await.unwrap();
+                    }
+                }
+            }
+        }
+    }
+}
+
+async fn handle_request(
+    request: Request,
+    config: &FilesystemConfig,
+    watches: &mut Watches,
+    event_tx: &mpsc::Sender<Event>,
+) {
+    use Request::*;
+
+    match request {
+        Scan => {
+            event_tx.send(Event::ScanStarted).await.unwrap();
+
+            let roots = config
+                .scanner
+                .roots
+                .iter()
+                .map(|rootdir| PathBuf::from(shellexpand::tilde(&rootdir).into_owned()))
+                .collect::<VecDeque<_>>();
+            scan(roots, config, watches, event_tx).await;
+
+            event_tx.send(Event::ScanCompleted).await.unwrap();
+        }
+
+        // edits run alongside any scan in progress
+        RewriteRemoteUrls(rewrite, paths) => {
+            tokio::spawn(rewrite_remote_urls(rewrite, paths, event_tx.clone()));
+        }
+
+        EditRemote(path, edit) => {
+            tokio::spawn(edit_remote(path, edit, event_tx.clone()));
+        }
+    }
+}
+
+/// Find repos below the pending dirs, watching each directory traversed on the way.
+async fn scan(
+    mut pending_dirs: VecDeque<PathBuf>,
+    config: &FilesystemConfig,
+    watches: &mut Watches,
+    event_tx: &mpsc::Sender<Event>,
+) {
+    while let Some(dir) = pending_dirs.pop_front() {
+        let excluded = config.scanner.excludes.is_match(&dir);
+        trace!("considering {:?}", &dir);
+        if !excluded {
+            trace!("included {:?}", &dir);
+            let git_dir = dir.join(".git");
+
+            if is_primary_git_worktree(&git_dir).await {
+                watches.watch_repo(&dir);
+                let repo = local_repo(dir).await;
+                event_tx.send(Event::LocalRepo(repo)).await.unwrap();
+            } else {
+                watches.watch(&dir);
+                match read_subdirs(&dir).await {
+                    Ok(subdirs) => {
+                        pending_dirs.extend(subdirs);
+                    }
+                    Err(e) => {
+                        error!("read_subdirs: {}", e)
+                    }
+                }
+            }
+        }
+    }
+}
+
+async fn handle_change(
+    change: Change,
+    config: &FilesystemConfig,
+    watches: &mut Watches,
+    event_tx: &mpsc::Sender<Event>,
+) {
+    trace!("{:?}", &change);
+
+    match change {
+        Change::Created(path) => {
+            if path.file_name().is_some_and(|name| name == ".git") {
+                // a clone in progress, or git init, in a directory we're watching
+                if let Some(dir) = path.parent() {
+                    if !config.scanner.excludes.is_match(dir) {
+                        if is_primary_git_worktree(&path).await {
+                            // repos aren't traversed, so only their git dir is watched
+                            watches.unwatch_all(dir);
+                            watches.watch_repo(dir);
+                            let repo = local_repo(dir.to_path_buf()).await;
                             event_tx.send(Event::LocalRepo(repo)).await.unwrap();
-                        } else {
-                            match read_subdirs(&dir).await {
-                                Ok(subdirs) => {
-                                    pending_dirs.extend(subdirs);
-                                }
-                                Err(e) => {
-                                    error!("read_subdirs: {}", e)
-                                }
-                            }
+                        } else if is_dir(&path).await {
+                            // git hasn't filled it in yet, so look again once HEAD appears
+                            watches.watch(&path);
                         }
                     }
                 }
+            } else if is_dir(&path).await {
+                scan(VecDeque::from([path]), config, watches, event_tx).await;
             }
         }
+
+        Change::Removed(path) => {
+            watches.unwatch_all(&path);
+            if path.file_name().is_some_and(|name| name == ".git") {
+                // no longer a repo, but may contain some
+                if let Some(dir) = path.parent() {
+                    event_tx
+                        .send(Event::Removed(dir.to_path_buf()))
+                        .await
+                        .unwrap();
+                    scan(
+                        VecDeque::from([dir.to_path_buf()]),
+                        config,
+                        watches,
+                        event_tx,
+                    )
+                    .await;
+                }
+            } else {
+                event_tx.send(Event::Removed(path)).await.unwrap();
+            }
+        }
+
+        // only of interest within repos
+        Change::Modified(_) => (),
     }
 }
 
+async fn rewrite_remote_urls(
+    rewrite: UrlRewrite,
+    paths: Vec<PathBuf>,
+    event_tx: mpsc::Sender<Event>,
+) {
+    let (mut n_urls, mut n_repos, mut n_failed) = (0, 0, 0);
+
+    for path in paths {
+        let rewrite = rewrite.clone();
+        let path_ = path.clone();
+        match spawn_blocking(move || git::rewrite_remote_urls(&path_, &rewrite)).await {
+            Ok(Ok(n)) => {
+                if n > 0 {
+                    n_urls += n;
+                    n_repos += 1;
+                }
+            }
+            Ok(Err(e)) => {
+                warn!("rewrite_remote_urls failed on {:?}: {:#}", &path, e);
+                n_failed += 1;
+            }
+            Err(e) => {
+                warn!("spawn_blocking rewrite_remote_urls failed: {}", e);
+                n_failed += 1;
+            }
+        }
+
+        let repo = local_repo(path).await;
+        event_tx.send(Event::LocalRepo(repo)).await.unwrap();
+    }
+
+    let summary = format!("rewrote {} remote URLs in {} repos", n_urls, n_repos);
+    event_tx
+        .send(if n_failed == 0 {
+            Event::Info(summary)
+        } else {
+            Event::Error(format!("{}, {} repos failed", summary, n_failed))
+        })
+        .await
+        .unwrap();
+}
+
+async fn edit_remote(path: PathBuf, edit: RemoteEdit, event_tx: mpsc::Sender<Event>) {
+    let path_ = path.clone();
+    let edit_ = edit.clone();
+    let ev = match spawn_blocking(move || git::edit_remote(&path_, &edit_)).await {
+        Ok(Ok(())) => Event::Info(format!("{}", edit)),
+        Ok(Err(e)) => {
+            warn!("edit_remote failed on {:?}: {:#}", &path, e);
+            Event::Error(format!("failed to {}: {:#}", edit, e))
+        }
+        Err(e) => {
+            warn!("spawn_blocking edit_remote failed: {}", e);
+            Event::Error(format!("failed to {}", edit))
+        }
+    };
+    event_tx.send(ev).await.unwrap();
+
+    let repo = local_repo(path).await;
+    event_tx.send(Event::LocalRepo(repo)).await.unwrap();
+}
+
 #[tracing::instrument(level = "trace")]
 async fn read_subdirs<P>(dir: P) -> Result<Vec<PathBuf>>
 where
@@ -163,30 +374,13 @@ where
 }
 
 #[tracing::instrument(level = "trace")]
-async fn git_remotes(path: PathBuf) -> Vec<Remote> {
-    match spawn_blocking(move || match gix::discover(&path) {
-        Ok(repo) => repo
-            .remote_names()
-            .into_iter()
-            .filter_map(|name| {
-                let name: &gix::bstr::BStr = name.borrow();
-                let remote = repo.find_remote(name).unwrap();
-                remote
-                    .url(gix::remote::Direction::Push)
-                    .map(|url| Remote::new(name, url))
-            })
-            .collect::<Vec<_>>(),
-        Err(e) => {
-            warn!("git_remotes failed on {:?}: {}", &path, e);
-            Vec::default()
-        }
-    })
-    .await
-    {
-        Ok(remotes) => remotes,
+pub async fn local_repo(path: PathBuf) -> LocalRepo {
+    let fallback_path = path.clone();
+    match spawn_blocking(move || git::local_repo(path)).await {
+        Ok(repo) => repo,
         Err(e) => {
-            warn!("spawn_blocking git_remotes failed: {}", e);
-            Vec::default()
+            warn!("spawn_blocking local_repo failed: {}", e);
+            LocalRepo::new(fallback_path, Vec::default(), RepoStatus::default())
         }
     }
 }
@@ -0,0 +1,171 @@
+use notify::{
+    event::{ModifyKind, RenameMode},
+    EventKind, RecommendedWatcher, RecursiveMode, Watcher,
+};
+use std::{
+    collections::BTreeSet,
+    path::{Path, PathBuf},
+};
+use tokio::sync::mpsc;
+use tracing::{trace, warn};
+
+/// Something appearing, disappearing, or changing in a watched directory.
+#[derive(PartialEq, Eq, Debug)]
+pub enum Change {
+    Created(PathBuf),
+    Removed(PathBuf),
+    Modified(PathBuf),
+}
+
+/// Non-recursive watches on directories, so that excluded subtrees aren't watched,
+/// along with the parts of each repo's git dir which affect its status and remotes.
+pub struct Watches {
+    watcher: Option<RecommendedWatcher>,
+    watched: BTreeSet<PathBuf>,
+}
+
+impl Watches {
+    /// Changes are sent on `change_tx`, or if watching isn't possible, nothing is watched.
+    pub fn new(change_tx: mpsc::UnboundedSender<Change>) -> Self {
+        let watcher = notify::recommended_watcher(move |ev: notify::Result<notify::Event>| {
+            match ev {
+                Ok(ev) => {
+                    for change in changes(ev) {
+                        // receiver only goes away on shutdown
+                        let _ = change_tx.send(change);
+                    }
+                }
+                Err(e) => warn!("filesystem watch error: {}", e),
+            }
+        });
+        let watcher = watcher
+            .inspect_err(|e| warn!("filesystem watching unavailable: {}", e))
+            .ok();
+
+        Self {
+            watcher,
+            watched: BTreeSet::default(),
+        }
+    }
+
+    pub fn watch(&mut self, dir: &Path) {
+        self.watch_with(dir, RecursiveMode::NonRecursive);
+    }
+
+    /// Watch `HEAD`, `index`, `config` and everything under `refs` in the repo's git dir.
+    pub fn watch_repo(&mut self, repo_dir: &Path) {
+        let git_dir = repo_dir.join(".git");
+        self.watch_with(&git_dir, RecursiveMode::NonRecursive);
+        self.watch_with(&git_dir.join("refs"), RecursiveMode::Recursive);
+    }
+
+    fn watch_with(&mut self, dir: &Path, mode: RecursiveMode) {
+        if let Some(watcher) = self.watcher.as_mut() {
+            if !self.watched.contains(dir) {
+                trace!("watching {:?}", dir);
+                match watcher.watch(dir, mode) {
+                    Ok(()) => {
+                        self.watched.insert(dir.to_path_buf());
+                    }
+                    Err(e) => warn!("failed to watch {:?}: {}", dir, e),
+                }
+            }
+        }
+    }
+
+    pub fn is_watched(&self, dir: &Path) -> bool {
+        self.watched.contains(dir)
+    }
+
+    /// Stop watching `path` and everything below it, including any repos.
+    pub fn unwatch_all(&mut self, path: &Path) {
+        let below = self
+            .watched
+            .range(path.to_path_buf()..)
+            .take_while(|watched| watched.starts_with(path))
+            .cloned()
+            .collect::<Vec<_>>();
+        for dir in below {
+            self.watched.remove(&dir);
+            if let Some(watcher) = self.watcher.as_mut() {
+                // fails harmlessly if the directory has gone, as the watch went with it
+                let _ = watcher.unwatch(&dir);
+            }
+        }
+    }
+}
+
+fn changes(ev: notify::Event) -> Vec<Change> {
+    use Change::*;
+
+    let mut paths = ev.paths.into_iter();
+    match ev.kind {
+        EventKind::Create(_) | EventKind::Modify(ModifyKind::Name(RenameMode::To)) => {
+            paths.map(Created).collect()
+        }
+        EventKind::Remove(_) | EventKind::Modify(ModifyKind::Name(RenameMode::From)) => {
+            paths.map(Removed).collect()
+        }
+        EventKind::Modify(ModifyKind::Name(RenameMode::Both)) => {
+            match (paths.next(), paths.next()) {
+                (Some(from), Some(to)) => vec![Removed(from), Created(to)],
+                _ => Vec::default(),
+            }
+        }
+        EventKind::Modify(ModifyKind::Data(_) | ModifyKind::Any) => paths.map(Modified).collect(),
+        _ => Vec::default(),
+    }
+}
+
+impl Change {
+    pub fn path(&self) -> &Path {
+        use Change::*;
+        match self {
+            Created(path) | Removed(path) | Modified(path) => path,
+        }
+    }
+}
+
+/// The repo whose git dir contains `path`, if any.
+pub fn repo_containing(path: &Path) -> Option<&Path> {
+    path.ancestors()
+        .skip(1)
+        .find(|ancestor| ancestor.file_name().is_some_and(|name| name == ".git"))
+        .and_then(Path::parent)
+}
+
+/// Whether `path` is one of the lock files which git creates and removes all the time.
+pub fn is_lock_file(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "lock")
+}
+
+#[cfg(test)]
+mod tests {
+    use notify::event::{CreateKind, RemoveKind};
+    use test_case::test_case;
+
+    use super::{Change::*, *};
+
+    #[test_case(EventKind::Create(CreateKind::Folder), vec!["/a/b"], vec![Created(PathBuf::from("/a/b"))])]
+    #[test_case(EventKind::Remove(RemoveKind::Any), vec!["/a/b"], vec![Removed(PathBuf::from("/a/b"))])]
+    #[test_case(EventKind::Modify(ModifyKind::Name(RenameMode::Both)), vec!["/a/b", "/a/c"], vec![Removed(PathBuf::from("/a/b")), Created(PathBuf::from("/a/c"))]; "rename")]
+    #[test_case(EventKind::Modify(ModifyKind::Any), vec!["/a/b"], vec![Modified(PathBuf::from("/a/b"))]; "content change")]
+    #[test_case(EventKind::Access(notify::event::AccessKind::Any), vec!["/a/b"], vec![]; "access")]
+    fn test_changes(kind: EventKind, paths: Vec<&str>, expected: Vec<Change>) {
+        let ev = paths
+            .into_iter()
+            .fold(notify::Event::new(kind), |ev, path| {
+                ev.add_path(PathBuf::from(path))
+            });
+        assert_eq!(changes(ev), expected);
+    }
+
+    #[test_case("/a/r/.git/HEAD", Some("/a/r"))]
+    #[test_case("/a/r/.git/refs/heads/main", Some("/a/r"))]
+    #[test_case("/a/r/.git/index.lock", Some("/a/r"); "lock file")]
+    #[test_case("/a/r/.git", None; "git dir itself")]
+    #[test_case("/a/r/src", None; "worktree")]
+    fn test_repo_containing(path: &str, expected: Option<&str>) {
+        assert_eq!(repo_containing(Path::new(path)), expected.map(Path::new));
+    }
+}
@@ -0,0 +1,152 @@
+use futures::StreamExt;
+use std::future::Future;
+use tokio::sync::mpsc;
+use tracing::{debug, warn};
+
+use crate::{
+    cache,
+    config::GitHubConfig,
+    github::Connection,
+    model::{GitHubRepo, UpdateGitHub},
+    service::Notify,
+};
+
+pub enum Request {
+    /// list the user's repos, replacing any earlier listing
+    List,
+}
+
+pub enum Event {
+    ListStarted,
+    Repo(GitHubRepo),
+    ListCompleted,
+    /// the listing failed part way, so is incomplete
+    ListFailed(String),
+}
+
+pub struct Service {
+    event_rx: mpsc::Receiver<Event>,
+    request_tx: mpsc::UnboundedSender<Request>,
+}
+
+impl Service {
+    pub fn new(config: &GitHubConfig) -> Self {
+        let (event_tx, event_rx) = mpsc::channel(1);
+        let (request_tx, request_rx) = mpsc::unbounded_channel();
+
+        tokio::spawn(worker(config.clone(), request_rx, event_tx));
+
+        Self {
+            event_rx,
+            request_tx,
+        }
+    }
+
+    pub fn requester(&self) -> mpsc::UnboundedSender<Request> {
+        self.request_tx.clone()
+    }
+
+    pub fn recv_event(&mut self) -> impl Future<Output = Option<Event>> + '_ {
+        self.event_rx.recv()
+    }
+
+    pub async fn handle<M>(&mut self, ev: Event, model: &mut M)
+    where
+        M: UpdateGitHub + Notify,
+    {
+        match ev {
+            Event::ListStarted => model.github_listing_started(),
+            Event::Repo(repo) => model.add_github_repo(repo),
+            Event::ListCompleted => model.github_listing_completed(true),
+            Event::ListFailed(message) => {
+                model.github_listing_completed(false);
+                model.error(message);
+            }
+        }
+    }
+}
+
+async fn worker(
+    config: GitHubConfig,
+    mut request_rx: mpsc::UnboundedReceiver<Request>,
+    event_tx: mpsc::Sender<Event>,
+) {
+    // connected on first use, since finding a token may run `gh` or git credential helpers
+    let mut connection = None;
+
+    while let Some(request) = request_rx.recv().await {
+        match request {
+            Request::List => {
+                if connection.is_none() {
+                    let config = config.clone();
+                    match tokio::task::spawn_blocking(move || Connection::new(&config)).await {
+                        Ok(Ok(connected)) => {
+                            restore_budget(&connected);
+                            connection = Some(connected);
+                        }
+                        Ok(Err(e)) => {
+                            warn!("{}", e);
+                            event_tx
+                                .send(Event::ListFailed(e.to_string()))
+                                .await
+                                .unwrap();
+                            continue;
+                        }
+                        Err(e) => panic!("GitHub client creation panicked: {}", e),
+                    }
+                }
+                if let Some(connection) = connection.as_ref() {
+                    list(connection, config.user.as_deref(), &event_tx).await;
+                    save_budget(connection);
+                }
+            }
+        }
+    }
+}
+
+async fn list(connection: &Connection, user: Option<&str>, event_tx: &mpsc::Sender<Event>) {
+    event_tx.send(Event::ListStarted).await.unwrap();
+
+    let repos = connection.repos(user);
+    tokio::pin!(repos);
+    let mut n_repos = 0;
+    while let Some(repo) = repos.next().await {
+        match repo {
+            Ok(repo) => {
+                n_repos += 1;
+                event_tx.send(Event::Repo(repo)).await.unwrap();
+            }
+            Err(e) => {
+                warn!("GitHub listing failed: {}", e);
+                event_tx
+                    .send(Event::ListFailed(e.to_string()))
+                    .await
+                    .unwrap();
+                return;
+            }
+        }
+    }
+
+    debug!("listed {} GitHub repos", n_repos);
+    event_tx.send(Event::ListCompleted).await.unwrap();
+}
+
+/// Continue with what's left of the rate limit from an earlier run.
+fn restore_budget(connection: &Connection) {
+    match cache::read_github_budgets() {
+        Ok(Some(budgets)) => connection.restore_budget(&budgets),
+        Ok(None) => (),
+        Err(e) => warn!("ignoring GitHub rate limit cache: {}", e),
+    }
+}
+
+fn save_budget(connection: &Connection) {
+    let mut budgets = cache::read_github_budgets()
+        .ok()
+        .flatten()
+        .unwrap_or_default();
+    connection.save_budget(&mut budgets);
+    if let Err(e) = cache::write_github_budgets(&budgets) {
+        warn!("failed to save GitHub rate limits: {}", e);
+    }
+}
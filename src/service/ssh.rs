@@ -0,0 +1,338 @@
+use futures::StreamExt;
+use russh_sftp::client::error::Error as SftpError;
+use std::{collections::HashMap, future::Future, sync::Arc};
+use tokio::{
+    select,
+    sync::{mpsc, Mutex},
+};
+use tracing::{debug, trace, warn};
+
+use crate::{
+    config::{SshConfig, SshHostConfig},
+    model::{LocalRepo, UpdateModel},
+    service::Notify,
+    ssh::{self, ConnectOptions, Connection, Question},
+};
+
+pub enum Request {
+    /// scan all configured hosts
+    Scan,
+}
+
+pub enum Event {
+    ScanStarted(String),
+    /// a repo found on the host it is marked with
+    LocalRepo(LocalRepo),
+    ScanCompleted(String),
+    /// the scan of the host ended early, so not all its repos were found
+    ScanFailed(String, String),
+    /// for the user to answer
+    Ask(Question),
+    Error(String),
+}
+
+/// Receiver of questions which only the user can answer.
+pub trait AskUser {
+    fn ask(&mut self, question: Question);
+}
+
+pub struct Service {
+    event_rx: mpsc::Receiver<Event>,
+    request_tx: mpsc::UnboundedSender<Request>,
+}
+
+impl Service {
+    pub fn new(config: &SshConfig) -> Self {
+        let (event_tx, event_rx) = mpsc::channel(1);
+        let (request_tx, request_rx) = mpsc::unbounded_channel();
+
+        tokio::spawn(worker(config.clone(), request_rx, event_tx));
+
+        Self {
+            event_rx,
+            request_tx,
+        }
+    }
+
+    pub fn requester(&self) -> mpsc::UnboundedSender<Request> {
+        self.request_tx.clone()
+    }
+
+    pub fn recv_event(&mut self) -> impl Future<Output = Option<Event>> + '_ {
+        self.event_rx.recv()
+    }
+
+    pub async fn handle<M>(&mut self, ev: Event, model: &mut M)
+    where
+        M: UpdateModel + Notify + AskUser,
+    {
+        match ev {
+            Event::ScanStarted(host) => model.scan_started(Some(&host)),
+            Event::LocalRepo(repo) => model.add_local_repo(repo),
+            Event::ScanCompleted(host) => model.scan_completed(Some(&host)),
+            Event::ScanFailed(host, message) => {
+                model.scan_failed(Some(&host));
+                model.error(message);
+            }
+            Event::Ask(question) => model.ask(question),
+            Event::Error(message) => model.error(message),
+        }
+    }
+}
+
+/// What's kept between scans of a host.
+#[derive(Default)]
+struct Host {
+    /// kept for later scans, locked while connecting
+    connection: Arc<Mutex<Option<Arc<Connection>>>>,
+    /// held while scanning, so a rescan can't overlap a scan still running, whose completion
+    /// would mark as vanished repos which the rescan hadn't yet found again
+    scanning: Arc<Mutex<()>>,
+}
+
+async fn worker(
+    config: SshConfig,
+    mut request_rx: mpsc::UnboundedReceiver<Request>,
+    event_tx: mpsc::Sender<Event>,
+) {
+    let mut hosts = HashMap::<String, Host>::new();
+
+    while let Some(request) = request_rx.recv().await {
+        match request {
+            Request::Scan => {
+                // hosts are scanned concurrently, so a slow one doesn't hold up the rest
+                for host_config in config.hosts.iter() {
+                    let host = hosts.entry(host_config.host.clone()).or_default();
+                    let scan = scan(
+                        host_config.clone(),
+                        config.trust_on_first_use,
+                        host.connection.clone(),
+                        event_tx.clone(),
+                    );
+                    let scanning = host.scanning.clone();
+                    let host = host_config.host.clone();
+                    tokio::spawn(async move {
+                        if exclusively(scanning, scan).await.is_none() {
+                            debug!("{} is still being scanned, so not scanning again", host);
+                        }
+                    });
+                }
+            }
+        }
+    }
+}
+
+async fn scan(
+    config: SshHostConfig,
+    trust_on_first_use: bool,
+    slot: Arc<Mutex<Option<Arc<Connection>>>>,
+    event_tx: mpsc::Sender<Event>,
+) {
+    let host = config.host.clone();
+
+    let connection = match reuse_or_connect(&slot, Connection::is_closed, || {
+        connect(&config, trust_on_first_use, &event_tx)
+    })
+    .await
+    {
+        Ok(connection) => connection,
+        Err(e) => {
+            warn!("failed to connect to {}: {}", &host, e);
+            event_tx
+                .send(Event::Error(format!("ssh {}: {}", &host, e)))
+                .await
+                .unwrap();
+            return;
+        }
+    };
+
+    event_tx
+        .send(Event::ScanStarted(host.clone()))
+        .await
+        .unwrap();
+
+    let home = connection.home_dir().await;
+    let excludes = match config.excludes_on_host(&home) {
+        Ok(excludes) => excludes,
+        Err(e) => {
+            // validated when reading config, so only a change of home could cause this
+            event_tx
+                .send(Event::ScanFailed(host, e.to_string()))
+                .await
+                .unwrap();
+            return;
+        }
+    };
+
+    let repos = connection
+        .git_dirs(config.roots_on_host(&home), &excludes, config.max_depth)
+        .map(|git_dir| {
+            let (connection, host) = (&connection, config.host.as_str());
+            async move {
+                let git_dir = git_dir?;
+                trace!("found {}:{} bare={}", host, &git_dir.path, git_dir.bare);
+                Ok::<_, SftpError>(connection.repo(&git_dir).await)
+            }
+        })
+        .buffer_unordered(connection.max_in_flight());
+    tokio::pin!(repos);
+    while let Some(repo) = repos.next().await {
+        match repo {
+            Ok(repo) => event_tx
+                .send(Event::LocalRepo(repo.on_host(&host)))
+                .await
+                .unwrap(),
+            Err(e) => {
+                warn!("scan of {} failed: {}", &host, e);
+                // don't reuse a connection which may be broken
+                slot.lock().await.take();
+                event_tx
+                    .send(Event::ScanFailed(
+                        host.clone(),
+                        format!("ssh {}: {}", &host, e),
+                    ))
+                    .await
+                    .unwrap();
+                return;
+            }
+        }
+    }
+
+    event_tx.send(Event::ScanCompleted(host)).await.unwrap();
+}
+
+/// Run `task` unless another holding the lock is still running, returning its output if run.
+async fn exclusively<F>(lock: Arc<Mutex<()>>, task: F) -> Option<F::Output>
+where
+    F: Future,
+{
+    let _guard = lock.try_lock_owned().ok()?;
+    Some(task.await)
+}
+
+/// The connection in the slot unless it has since closed, otherwise a new one, kept for next time.
+async fn reuse_or_connect<C, F, Fut>(
+    slot: &Mutex<Option<Arc<C>>>,
+    is_closed: fn(&C) -> bool,
+    connect: F,
+) -> anyhow::Result<Arc<C>>
+where
+    F: FnOnce() -> Fut,
+    Fut: Future<Output = anyhow::Result<C>>,
+{
+    let mut slot = slot.lock().await;
+    match slot.as_ref().filter(|connection| !is_closed(connection)) {
+        Some(connection) => {
+            debug!("reusing connection");
+            Ok(connection.clone())
+        }
+        None => Ok(slot.insert(Arc::new(connect().await?)).clone()),
+    }
+}
+
+/// Connect to the host, passing on any questions for the user meanwhile.
+async fn connect(
+    config: &SshHostConfig,
+    trust_on_first_use: bool,
+    event_tx: &mpsc::Sender<Event>,
+) -> anyhow::Result<Connection> {
+    let options = ConnectOptions {
+        user: config.user.clone(),
+        trust_on_first_use,
+        password_auth: config.password_auth,
+        max_in_flight: config.max_in_flight,
+    };
+
+    let (ask_tx, mut ask_rx) = mpsc::channel(1);
+    let connect = ssh::connect(&config.host, &options, Some(ask_tx));
+    tokio::pin!(connect);
+    loop {
+        select! {
+            connected = &mut connect => return connected,
+            Some(question) = ask_rx.recv() => {
+                event_tx.send(Event::Ask(question)).await.unwrap();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::anyhow;
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+    use super::*;
+
+    struct FakeConnection {
+        id: usize,
+        closed: AtomicBool,
+    }
+
+    impl FakeConnection {
+        fn is_closed(&self) -> bool {
+            self.closed.load(Ordering::SeqCst)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_reuse_or_connect() {
+        let slot = Mutex::new(None);
+        let n_connects = AtomicUsize::new(0);
+        let connect = || async {
+            let id = n_connects.fetch_add(1, Ordering::SeqCst);
+            Ok(FakeConnection {
+                id,
+                closed: AtomicBool::new(false),
+            })
+        };
+
+        let first = reuse_or_connect(&slot, FakeConnection::is_closed, connect)
+            .await
+            .unwrap();
+        let second = reuse_or_connect(&slot, FakeConnection::is_closed, connect)
+            .await
+            .unwrap();
+        assert_eq!((first.id, second.id), (0, 0));
+        assert_eq!(n_connects.load(Ordering::SeqCst), 1);
+
+        // reconnected once closed, as when the host dropped the connection between scans
+        second.closed.store(true, Ordering::SeqCst);
+        let third = reuse_or_connect(&slot, FakeConnection::is_closed, connect)
+            .await
+            .unwrap();
+        assert_eq!(third.id, 1);
+
+        // a failure to connect leaves the closed connection to be replaced next time
+        third.closed.store(true, Ordering::SeqCst);
+        let failed = reuse_or_connect(&slot, FakeConnection::is_closed, || async {
+            Err::<FakeConnection, _>(anyhow!("refused"))
+        })
+        .await;
+        assert!(failed.is_err());
+        let fourth = reuse_or_connect(&slot, FakeConnection::is_closed, connect)
+            .await
+            .unwrap();
+        assert_eq!(fourth.id, 2);
+    }
+
+    #[tokio::test]
+    async fn test_exclusively() {
+        let lock = Arc::new(Mutex::new(()));
+        let (release_tx, release_rx) = tokio::sync::oneshot::channel::<()>();
+        let first = tokio::spawn(exclusively(lock.clone(), async move {
+            release_rx.await.unwrap();
+            "first"
+        }));
+        tokio::task::yield_now().await;
+
+        // skipped while the first is still running, as with a rescan during a scan
+        assert_eq!(exclusively(lock.clone(), async { "second" }).await, None);
+
+        release_tx.send(()).unwrap();
+        assert_eq!(first.await.unwrap(), Some("first"));
+        assert_eq!(
+            exclusively(lock.clone(), async { "third" }).await,
+            Some("third")
+        );
+    }
+}
@@ -85,18 +85,18 @@ impl Service {
         self.receiver.recv()
     }
 
-    pub async fn handle<F>(&mut self, ev: Event, key_handler: F) -> bool
+    pub async fn handle<F, T>(&mut self, ev: Event, key_handler: F) -> Option<T>
     where
-        F: FnOnce(KeyEvent) -> bool,
+        F: FnOnce(KeyEvent) -> Option<T>,
     {
         use Event::*;
-        let mut quit = false;
+        let mut action = None;
         match ev {
-            Key(key_event) => quit = key_handler(key_event),
+            Key(key_event) => action = key_handler(key_event),
             Mouse(_) => {}
             Resize(_, _) => {}
         }
 
-        quit
+        action
     }
 }
@@ -1,64 +1,145 @@
-use anyhow::{anyhow, Result};
+use anyhow::{anyhow, bail, Result};
 use async_stream::stream;
 use async_trait::async_trait;
-use futures::Stream;
+use futures::{
+    future::{try_join_all, BoxFuture},
+    stream::FuturesUnordered,
+    FutureExt, Stream, StreamExt,
+};
 use globset::GlobSet;
 use russh::{
     client,
-    keys::{agent::client::AgentClient, key},
+    keys::{key, PublicKeyBase64},
     ChannelId,
 };
-use russh_sftp::client::SftpSession;
+use russh_sftp::{
+    client::{error::Error as SftpError, SftpSession},
+    protocol::StatusCode,
+};
 use std::{collections::VecDeque, fmt::Debug, path::Path, sync::Arc};
-use tracing::{debug, info};
+use tokio::sync::{mpsc, oneshot, Semaphore, SemaphorePermit};
+use tracing::{debug, info, trace};
 
-pub struct Connection {
-    _session: client::Handle<Client>,
-    sftp_session: SftpSession,
+mod auth;
+mod host_config;
+mod known_hosts;
+mod remote_repo;
+use host_config::{local_user, HostConfigs, HostParams};
+use known_hosts::{KnownHosts, Verdict};
+
+/// How to connect, beyond what the ssh config says.
+#[derive(Clone, Debug)]
+pub struct ConnectOptions {
+    /// overriding any from the ssh config
+    pub user: Option<String>,
+    /// ask whether to trust host keys not in known_hosts, rather than refusing them
+    pub trust_on_first_use: bool,
+    /// after keys, try keyboard-interactive and password authentication
+    pub password_auth: bool,
+    /// maximum number of SFTP requests awaiting a reply at once
+    pub max_in_flight: usize,
 }
 
-impl Debug for Connection {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.write_str("Connection")
-    }
+/// Something only the user can answer, with a connection waiting on the reply.
+#[derive(Debug)]
+pub enum Question {
+    TrustHostKey(UnknownHostKey),
+    Secret(SecretPrompt),
 }
 
-async fn connect<S1, S2>(user: S1, host: S2) -> Result<Connection>
-where
-    S1: AsRef<str>,
-    S2: AsRef<str>,
-{
-    let user = user.as_ref();
-    let host = host.as_ref();
+/// A host key not in known_hosts, awaiting the user's decision whether to trust it.
+#[derive(Debug)]
+pub struct UnknownHostKey {
+    pub host: String,
+    pub key_type: &'static str,
+    /// SHA256, as shown by ssh
+    pub fingerprint: String,
+    pub reply: oneshot::Sender<bool>,
+}
 
-    let mut agent = AgentClient::connect_env().await?;
-    let identities = agent.request_identities().await?;
+/// A passphrase, password, or whatever else the server asks, or none if the user declines.
+#[derive(Debug)]
+pub struct SecretPrompt {
+    pub host: String,
+    pub prompt: String,
+    /// whether the answer may be shown as it is typed
+    pub echo: bool,
+    pub reply: oneshot::Sender<Option<String>>,
+}
 
-    let config = russh::client::Config::default();
-    let sh = Client {};
-    let mut session = russh::client::connect(Arc::new(config), (host, 22), sh).await?;
-    let mut authenticated = false;
+/// Where questions go, if there's anyone to ask.
+#[derive(Clone)]
+struct Asker(Option<mpsc::Sender<Question>>);
 
-    // use the first identity for which authentication succeeds
-    for id in identities {
-        debug!("trying identity {} {}", id.name(), id.fingerprint());
+impl Asker {
+    fn can_ask(&self) -> bool {
+        self.0.is_some()
+    }
 
-        let (agent_, authenticated_) = session.authenticate_future(user, id, agent).await;
-        agent = agent_;
-        authenticated = authenticated_?;
-        if authenticated {
-            debug!("auth succeded");
+    async fn ask<T>(&self, question: impl FnOnce(oneshot::Sender<T>) -> Question) -> Option<T> {
+        let ask_tx = self.0.as_ref()?;
+        let (reply_tx, reply_rx) = oneshot::channel();
+        ask_tx.send(question(reply_tx)).await.ok()?;
+        reply_rx.await.ok()
+    }
 
-            break;
-        } else {
-            debug!("auth failed");
-        }
+    async fn secret(&self, host: &str, prompt: String, echo: bool) -> Option<String> {
+        self.ask(|reply| {
+            Question::Secret(SecretPrompt {
+                host: host.to_string(),
+                prompt,
+                echo,
+                reply,
+            })
+        })
+        .await
+        .flatten()
     }
+}
+
+pub struct Connection {
+    /// sessions with any jump hosts, which must outlive the session tunnelled through them
+    _jumps: Vec<client::Handle<Client>>,
+    session: client::Handle<Client>,
+    sftp_session: SftpSession,
+    /// permits for SFTP requests, so as to keep many in flight over a slow link, but not too many
+    in_flight: Semaphore,
+    max_in_flight: usize,
+}
 
-    if !authenticated {
-        info!("all identities failed to authenticate");
-        return Err(anyhow!("all identities failed to authenticate"));
+impl Debug for Connection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Connection")
     }
+}
+
+/// Limit on `ProxyJump` hops, in case of a cycle in the ssh config.
+const MAX_JUMPS: usize = 8;
+
+/// Connect to `host` as resolved by `~/.ssh/config`, verifying host keys against `known_hosts` files.
+///
+/// Questions for the user go to `ask_tx`, without which unknown host keys are refused,
+/// encrypted key files skipped, and password authentication not attempted.
+pub async fn connect(
+    host: &str,
+    options: &ConnectOptions,
+    ask_tx: Option<mpsc::Sender<Question>>,
+) -> Result<Connection> {
+    let host_configs = HostConfigs::read_default()?;
+
+    let params = host_configs.resolve(host);
+    debug!("connecting to {:?}", &params);
+    let mut jumps = Vec::new();
+    let session = open_session(
+        &host_configs,
+        &params,
+        options.user.as_deref(),
+        options,
+        &Asker(ask_tx),
+        &mut jumps,
+        0,
+    )
+    .await?;
 
     let channel = session.channel_open_session().await?;
     channel.request_subsystem(true, "sftp").await?;
@@ -69,80 +150,279 @@ where
     );
 
     Ok(Connection {
-        _session: session,
+        _jumps: jumps,
+        session,
         sftp_session,
+        in_flight: Semaphore::new(options.max_in_flight),
+        max_in_flight: options.max_in_flight,
     })
 }
 
-impl Connection {
-    #[tracing::instrument(level = "trace")]
-    async fn read_subdirs<P>(&self, dir: P) -> Vec<String>
-    where
-        P: Into<String> + Debug,
-    {
-        let dir = dir.into();
-        if let Ok(rd) = self.sftp_session.read_dir(dir.as_str()).await {
-            rd.filter_map(|entry| {
-                entry
-                    .file_type()
-                    .is_dir()
-                    .then_some(entry.file_name())
-                    .map(|file_name| path_join(&dir, file_name))
-            })
-            .collect::<Vec<_>>()
-        } else {
-            Vec::default()
+/// Open an authenticated session, tunnelled through any jump hosts, whose sessions are kept in `jumps`.
+#[allow(clippy::too_many_arguments)]
+fn open_session<'a>(
+    host_configs: &'a HostConfigs,
+    params: &'a HostParams,
+    user: Option<&'a str>,
+    options: &'a ConnectOptions,
+    asker: &'a Asker,
+    jumps: &'a mut Vec<client::Handle<Client>>,
+    depth: usize,
+) -> BoxFuture<'a, Result<client::Handle<Client>>> {
+    async move {
+        if depth > MAX_JUMPS {
+            bail!("too many ProxyJump hops reaching {}", &params.host);
         }
+
+        let user = user
+            .map(str::to_string)
+            .or_else(|| params.user.clone())
+            .unwrap_or_else(local_user);
+        let sh = Client {
+            host: params.host_name.clone(),
+            port: params.port,
+            // as configured for this host, which may not be for its jump hosts
+            known_hosts: KnownHosts::read(
+                &params.user_known_hosts_files,
+                &params.global_known_hosts_files,
+            ),
+            trust_on_first_use: options.trust_on_first_use,
+            asker: asker.clone(),
+        };
+        let config = Arc::new(russh::client::Config::default());
+
+        let mut session = match params.proxy_jump.split_last() {
+            None => {
+                russh::client::connect(config, (params.host_name.as_str(), params.port), sh).await?
+            }
+            Some((last, via)) => {
+                // the last hop is reached through the others, or if none, whatever its own config says
+                let mut jump_params = host_configs.resolve(&last.host);
+                if let Some(port) = last.port {
+                    jump_params.port = port;
+                }
+                if !via.is_empty() {
+                    jump_params.proxy_jump = via.to_vec();
+                }
+                let jump = open_session(
+                    host_configs,
+                    &jump_params,
+                    last.user.as_deref(),
+                    options,
+                    asker,
+                    jumps,
+                    depth + 1,
+                )
+                .await?;
+                debug!("tunnelling to {} via {}", &params.host_name, &last.host);
+                let channel = jump
+                    .channel_open_direct_tcpip(
+                        params.host_name.as_str(),
+                        params.port as u32,
+                        "127.0.0.1",
+                        0,
+                    )
+                    .await?;
+                jumps.push(jump);
+                russh::client::connect_stream(config, channel.into_stream(), sh).await?
+            }
+        };
+
+        auth::authenticate(
+            &mut session,
+            &user,
+            &params.host,
+            &params.identity_files,
+            options,
+            asker,
+        )
+        .await?;
+        Ok(session)
     }
+    .boxed()
+}
 
-    #[tracing::instrument(level = "trace")]
-    async fn is_dir<P>(&self, path: P) -> bool
-    where
-        P: Into<String> + Debug,
-    {
+/// A repo found by traversal.
+#[derive(Debug)]
+pub struct GitDir {
+    pub path: String,
+    /// the path is the git dir itself, there being no worktree
+    pub bare: bool,
+}
+
+/// A directory entry, as far as finding repos is concerned.
+#[derive(Debug)]
+struct DirEntry {
+    name: String,
+    is_dir: bool,
+}
+
+impl Connection {
+    /// Whether the connection has been lost, so is no use for another scan.
+    pub fn is_closed(&self) -> bool {
+        self.session.is_closed()
+    }
+
+    pub fn max_in_flight(&self) -> usize {
+        self.max_in_flight
+    }
+
+    /// The home directory on the host, where SFTP sessions start, or `.` if it can't be found.
+    pub async fn home_dir(&self) -> String {
+        let _permit = self.permit().await;
         self.sftp_session
-            .symlink_metadata(path)
+            .canonicalize(".")
             .await
-            .is_ok_and(|m| m.is_dir())
+            .unwrap_or_else(|e| {
+                debug!("failed to find home directory: {}", e);
+                ".".to_string()
+            })
     }
-}
 
-pub fn git_dirs<S1, S2, I, P>(
-    user: S1,
-    host: S2,
-    rootdirs: I,
-    _excludes: GlobSet,
-) -> impl Stream<Item = String>
-where
-    S1: AsRef<str>,
-    S2: AsRef<str>,
-    I: IntoIterator<Item = P>,
-    P: AsRef<str>,
-{
-    let mut pending_dirs =
-        VecDeque::from_iter(rootdirs.into_iter().map(|dir| dir.as_ref().to_string()));
+    /// Wait for a permit to make an SFTP request.
+    async fn permit(&self) -> SemaphorePermit<'_> {
+        // never closed, so acquiring can't fail
+        self.in_flight.acquire().await.unwrap()
+    }
 
-    stream! {
-        let connection = connect(user, host).await.unwrap(); // TODO return error, or restructure to separate connect from stream
+    /// Entries of the directory, symlinks not being followed, so there's no danger of cycles.
+    ///
+    /// A directory which is missing or can't be read has no entries, but any other error,
+    /// such as the connection failing, is returned.
+    #[tracing::instrument(level = "trace")]
+    async fn read_dir(&self, dir: &str) -> Result<Vec<DirEntry>, SftpError> {
+        let read_dir = {
+            let _permit = self.permit().await;
+            self.sftp_session.read_dir(dir).await
+        };
+        let entries = match read_dir {
+            Ok(rd) => rd.collect::<Vec<_>>(),
+            Err(e) if is_about_path(&e) => {
+                debug!("failed to read {}: {}", dir, e);
+                return Ok(Vec::default());
+            }
+            Err(e) => return Err(e),
+        };
 
-        while let Some(dir)  = pending_dirs.pop_front() {
-            if dir.ends_with(".git") {
-                yield dir;
+        try_join_all(entries.into_iter().map(|entry| async move {
+            let name = entry.file_name();
+            let metadata = entry.metadata();
+            // some servers leave out attributes when listing, so the type must be asked for
+            let is_dir = if metadata.permissions.is_some() {
+                metadata.file_type().is_dir()
             } else {
-            let gitdir_candidate = path_join(&dir, ".git");
+                let path = path_join(dir, &name);
+                let _permit = self.permit().await;
+                match self.sftp_session.symlink_metadata(path.as_str()).await {
+                    Ok(metadata) => metadata.file_type().is_dir(),
+                    Err(e) if is_about_path(&e) => {
+                        debug!("failed to stat {}: {}", &path, e);
+                        false
+                    }
+                    Err(e) => return Err(e),
+                }
+            };
+            Ok(DirEntry { name, is_dir })
+        }))
+        .await
+    }
 
-            if connection.is_dir(gitdir_candidate).await {
-                yield dir;
-            } else {
-                let subdirs = connection.read_subdirs(&dir).await;
+    /// Repos at or below `rootdirs`, found breadth-first, skipping excluded paths,
+    /// and not looking deeper than `max_depth` below each root.
+    ///
+    /// Directories are read concurrently, up to the in-flight limit, so repos are found in no
+    /// particular order within each level.
+    ///
+    /// Ends with an error if one fails to be read for any reason but its own, such as the
+    /// connection failing, since then not all repos were found.
+    pub fn git_dirs<'a, I, P>(
+        &'a self,
+        rootdirs: I,
+        excludes: &'a GlobSet,
+        max_depth: Option<usize>,
+    ) -> impl Stream<Item = Result<GitDir, SftpError>> + 'a
+    where
+        I: IntoIterator<Item = P>,
+        P: AsRef<str>,
+    {
+        let mut pending_dirs = VecDeque::from_iter(
+            rootdirs
+                .into_iter()
+                .map(|dir| (dir.as_ref().to_string(), 0)),
+        );
 
-                pending_dirs.extend(subdirs);
-            }
+        stream! {
+            let mut reading = FuturesUnordered::new();
+            loop {
+                while reading.len() < self.max_in_flight {
+                    let Some((dir, depth)) = pending_dirs.pop_front() else {
+                        break;
+                    };
+                    if excludes.is_match(&dir) {
+                        trace!("excluded {}", &dir);
+                        continue;
+                    }
+                    reading.push(async move {
+                        let entries = self.read_dir(&dir).await;
+                        (dir, depth, entries)
+                    });
+                }
+
+                let Some((dir, depth, entries)) = reading.next().await else {
+                    break;
+                };
+                let entries = match entries {
+                    Ok(entries) => entries,
+                    Err(e) => {
+                        debug!("failed to read {}: {}", &dir, e);
+                        yield Err(e);
+                        break;
+                    }
+                };
+                match repo_layout(&entries) {
+                    Some(bare) => yield Ok(GitDir { path: dir, bare }),
+                    None => {
+                        if max_depth.is_none_or(|max_depth| depth < max_depth) {
+                            pending_dirs.extend(
+                                entries
+                                    .into_iter()
+                                    .filter(|entry| entry.is_dir)
+                                    .map(|entry| (path_join(&dir, entry.name), depth + 1)),
+                            );
+                        }
+                    }
+                }
             }
         }
     }
 }
 
+/// Whether the error is only about the path, its not existing or being unreadable,
+/// rather than the connection or server failing.
+fn is_about_path(e: &SftpError) -> bool {
+    matches!(
+        e,
+        SftpError::Status(status)
+            if matches!(status.status_code, StatusCode::NoSuchFile | StatusCode::PermissionDenied)
+    )
+}
+
+/// Whether the entries are those of a repo, and if so, whether it is bare.
+fn repo_layout(entries: &[DirEntry]) -> Option<bool> {
+    let has = |name: &str, is_dir: bool| {
+        entries
+            .iter()
+            .any(|entry| entry.name == name && entry.is_dir == is_dir)
+    };
+    if has(".git", true) {
+        Some(false)
+    } else if has("HEAD", false) && has("objects", true) && has("refs", true) {
+        Some(true)
+    } else {
+        None
+    }
+}
+
 fn path_join<S1, S2>(p1: S1, p2: S2) -> String
 where
     S1: AsRef<str>,
@@ -155,7 +435,13 @@ where
         .unwrap()
 }
 
-struct Client;
+struct Client {
+    host: String,
+    port: u16,
+    known_hosts: KnownHosts,
+    trust_on_first_use: bool,
+    asker: Asker,
+}
 
 // cribbed from https://github.com/AspectUnk/russh-sftp/blob/master/examples/client.rs
 #[async_trait]
@@ -166,12 +452,68 @@ impl client::Handler for Client {
         &mut self,
         server_public_key: &key::PublicKey,
     ) -> Result<bool, Self::Error> {
-        info!(
-            "skipping check of server key: {} {} ",
-            server_public_key.name(),
-            server_public_key.fingerprint()
-        );
-        Ok(true)
+        let key = server_public_key.public_key_bytes();
+        match self.known_hosts.verify(&self.host, self.port, &key) {
+            Verdict::Trusted => {
+                debug!("host key for {} is known", &self.host);
+                Ok(true)
+            }
+            Verdict::Mismatch { at } => Err(anyhow!(
+                "host key for {} does not match {}, refusing to connect",
+                &self.host,
+                at
+            )),
+            Verdict::Revoked { at } => Err(anyhow!(
+                "host key for {} is revoked in {}, refusing to connect",
+                &self.host,
+                at
+            )),
+            // russh doesn't negotiate host certificates, so the authority can't vouch for the host,
+            // and nor should its key be trusted on first use, as the authority is what's trusted
+            Verdict::CertAuthorityOnly { at } => Err(anyhow!(
+                "{} is known only by the certificate authority at {}, but host certificates aren't supported, refusing to connect",
+                &self.host,
+                at
+            )),
+            Verdict::Unknown => {
+                let key_type = server_public_key.name();
+                let fingerprint = format!("SHA256:{}", server_public_key.fingerprint());
+                if !self.trust_on_first_use || !self.asker.can_ask() {
+                    let known_hosts_paths = self
+                        .known_hosts
+                        .paths()
+                        .iter()
+                        .map(|path| path.display().to_string())
+                        .collect::<Vec<_>>();
+                    return Err(anyhow!(
+                        "host key {} {} for {} not found in {}, refusing to connect",
+                        key_type,
+                        fingerprint,
+                        &self.host,
+                        known_hosts_paths.join(", ")
+                    ));
+                }
+
+                let trusted = self
+                    .asker
+                    .ask(|reply| {
+                        Question::TrustHostKey(UnknownHostKey {
+                            host: self.host.clone(),
+                            key_type,
+                            fingerprint,
+                            reply,
+                        })
+                    })
+                    .await;
+                if trusted.unwrap_or(false) {
+                    info!("trusting host key for {} on first use", &self.host);
+                    self.known_hosts.append(&self.host, self.port, &key)?;
+                    Ok(true)
+                } else {
+                    Err(anyhow!("host key for {} not trusted", &self.host))
+                }
+            }
+        }
     }
 
     async fn data(
@@ -184,3 +526,80 @@ impl client::Handler for Client {
         Ok(())
     }
 }
+
+/// Glob match with `*` for any sequence and `?` for any single character.
+fn wildcard_match(pattern: &str, s: &str) -> bool {
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    let s = s.chars().collect::<Vec<_>>();
+
+    // position of the last `*`, and where in s it matched up to, for backtracking
+    let (mut p, mut i) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+    while i < s.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == s[i]) {
+            p += 1;
+            i += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, i));
+            p += 1;
+        } else if let Some((star_p, star_i)) = star {
+            p = star_p + 1;
+            i = star_i + 1;
+            star = Some((star_p, star_i + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+
+    #[test_case("*.example.com", "a.example.com", true)]
+    #[test_case("*.example.com", "example.com", false)]
+    #[test_case("build?", "build1", true)]
+    #[test_case("build?", "build10", false)]
+    #[test_case("b*d*1", "build1", true)]
+    #[test_case("*", "anything", true)]
+    fn test_wildcard_match(pattern: &str, s: &str, expected: bool) {
+        assert_eq!(wildcard_match(pattern, s), expected);
+    }
+
+    fn status(status_code: StatusCode) -> SftpError {
+        SftpError::Status(russh_sftp::protocol::Status {
+            id: 1,
+            status_code,
+            error_message: String::default(),
+            language_tag: String::default(),
+        })
+    }
+
+    #[test_case(status(StatusCode::NoSuchFile), true; "missing")]
+    #[test_case(status(StatusCode::PermissionDenied), true; "unreadable")]
+    #[test_case(status(StatusCode::ConnectionLost), false; "connection lost")]
+    #[test_case(SftpError::Timeout, false; "timeout")]
+    #[test_case(SftpError::IO("broken pipe".to_string()), false; "channel closed")]
+    fn test_is_about_path(e: SftpError, expected: bool) {
+        assert_eq!(is_about_path(&e), expected);
+    }
+
+    #[test_case(&[(".git", true), ("src", true)], Some(false); "worktree")]
+    #[test_case(&[("HEAD", false), ("objects", true), ("refs", true), ("config", false)], Some(true); "bare")]
+    #[test_case(&[("HEAD", false), ("objects", true)], None; "no refs")]
+    #[test_case(&[(".git", false), ("src", true)], None; "secondary worktree")]
+    #[test_case(&[("HEAD", true), ("objects", true), ("refs", true)], None; "HEAD dir")]
+    fn test_repo_layout(entries: &[(&str, bool)], expected: Option<bool>) {
+        let entries = entries
+            .iter()
+            .map(|(name, is_dir)| DirEntry {
+                name: name.to_string(),
+                is_dir: *is_dir,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(repo_layout(&entries), expected);
+    }
+}
@@ -0,0 +1,202 @@
+use anyhow::{anyhow, bail, Result};
+use russh::{
+    client::{self, KeyboardInteractiveAuthResponse},
+    keys::{agent::client::AgentClient, load_public_key, load_secret_key, PublicKeyBase64},
+};
+use std::{
+    collections::BTreeSet,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+use tracing::{debug, info};
+
+use super::{Asker, Client, ConnectOptions};
+
+/// Tried when the ssh config gives no `IdentityFile`, as with ssh.
+const DEFAULT_IDENTITY_FILES: [&str; 3] = ["id_ed25519", "id_ecdsa", "id_rsa"];
+
+/// Attempts at a passphrase before giving up on the key, as with ssh.
+const MAX_PASSPHRASE_ATTEMPTS: usize = 3;
+
+/// Authenticate with agent identities, then key files, then if enabled, keyboard-interactive and password.
+pub async fn authenticate(
+    session: &mut client::Handle<Client>,
+    user: &str,
+    host: &str,
+    identity_files: &[PathBuf],
+    options: &ConnectOptions,
+    asker: &Asker,
+) -> Result<()> {
+    let identity_files = if identity_files.is_empty() {
+        default_identity_files()
+    } else {
+        identity_files.to_vec()
+    };
+    let preferred = identity_files
+        .iter()
+        .filter_map(|path| load_public_key(public_key_path(path)).ok())
+        .map(|key| key.public_key_bytes())
+        .collect::<Vec<_>>();
+
+    // public keys already offered, so not worth a passphrase prompt for the file
+    let mut tried = BTreeSet::new();
+    if authenticate_with_agent(session, user, &preferred, &mut tried).await? {
+        return Ok(());
+    }
+
+    for path in identity_files.iter().filter(|path| path.exists()) {
+        if load_public_key(public_key_path(path))
+            .is_ok_and(|key| tried.contains(&key.public_key_bytes()))
+        {
+            continue;
+        }
+        if let Some(key) = load_key_file(path, host, asker).await {
+            debug!("trying key file {:?}", path);
+            if session.authenticate_publickey(user, Arc::new(key)).await? {
+                debug!("auth succeeded");
+                return Ok(());
+            }
+        }
+    }
+
+    if options.password_auth && asker.can_ask() {
+        if authenticate_interactively(session, user, host, asker).await? {
+            return Ok(());
+        }
+        if let Some(password) = asker
+            .secret(host, format!("password for {}@{}", user, host), false)
+            .await
+        {
+            if session.authenticate_password(user, password).await? {
+                debug!("auth succeeded");
+                return Ok(());
+            }
+        }
+    }
+
+    info!("all authentication methods failed");
+    Err(anyhow!("all authentication methods failed"))
+}
+
+/// Try each agent identity, those given by `IdentityFile` first, as with ssh.
+async fn authenticate_with_agent(
+    session: &mut client::Handle<Client>,
+    user: &str,
+    preferred: &[Vec<u8>],
+    tried: &mut BTreeSet<Vec<u8>>,
+) -> Result<bool> {
+    let mut agent = match AgentClient::connect_env().await {
+        Ok(agent) => agent,
+        Err(e) => {
+            debug!("no ssh agent: {}", e);
+            return Ok(false);
+        }
+    };
+    let mut identities = agent.request_identities().await?;
+    identities.sort_by_key(|id| !preferred.contains(&id.public_key_bytes()));
+
+    // use the first identity for which authentication succeeds
+    for id in identities {
+        debug!("trying identity {} {}", id.name(), id.fingerprint());
+        tried.insert(id.public_key_bytes());
+
+        let (agent_, authenticated) = session.authenticate_future(user, id, agent).await;
+        agent = agent_;
+        if authenticated? {
+            debug!("auth succeeded");
+            return Ok(true);
+        } else {
+            debug!("auth failed");
+        }
+    }
+    Ok(false)
+}
+
+/// Load the private key, asking for its passphrase if it is encrypted.
+async fn load_key_file(
+    path: &Path,
+    host: &str,
+    asker: &Asker,
+) -> Option<russh::keys::key::KeyPair> {
+    match load_secret_key(path, None) {
+        Ok(key) => return Some(key),
+        Err(russh::keys::Error::KeyIsEncrypted) => (),
+        Err(e) => {
+            debug!("can't load key file {:?}: {}", path, e);
+            return None;
+        }
+    }
+
+    for attempt in 0..MAX_PASSPHRASE_ATTEMPTS {
+        let prompt = if attempt == 0 {
+            format!("passphrase for {}", path.display())
+        } else {
+            format!("wrong passphrase, try again for {}", path.display())
+        };
+        let passphrase = asker.secret(host, prompt, false).await?;
+        match load_secret_key(path, Some(&passphrase)) {
+            Ok(key) => return Some(key),
+            Err(e) => debug!("failed to decrypt {:?}: {}", path, e),
+        }
+    }
+    None
+}
+
+/// Answer whatever the server asks, such as a password or one-time code.
+async fn authenticate_interactively(
+    session: &mut client::Handle<Client>,
+    user: &str,
+    host: &str,
+    asker: &Asker,
+) -> Result<bool> {
+    let mut response = session
+        .authenticate_keyboard_interactive_start(user, None)
+        .await?;
+    loop {
+        match response {
+            KeyboardInteractiveAuthResponse::Success => {
+                debug!("auth succeeded");
+                return Ok(true);
+            }
+            KeyboardInteractiveAuthResponse::Failure => return Ok(false),
+            KeyboardInteractiveAuthResponse::InfoRequest {
+                name,
+                instructions,
+                prompts,
+            } => {
+                let mut answers = Vec::new();
+                for prompt in prompts {
+                    let text = [name.as_str(), instructions.as_str(), prompt.prompt.trim()]
+                        .into_iter()
+                        .filter(|s| !s.is_empty())
+                        .collect::<Vec<_>>()
+                        .join("\n");
+                    match asker.secret(host, text, prompt.echo).await {
+                        Some(answer) => answers.push(answer),
+                        None => bail!("authentication to {} cancelled", host),
+                    }
+                }
+                response = session
+                    .authenticate_keyboard_interactive_respond(answers)
+                    .await?;
+            }
+        }
+    }
+}
+
+fn default_identity_files() -> Vec<PathBuf> {
+    dirs::home_dir()
+        .map(|home| {
+            DEFAULT_IDENTITY_FILES
+                .iter()
+                .map(|file| home.join(".ssh").join(file))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn public_key_path(path: &Path) -> PathBuf {
+    let mut public_path = path.as_os_str().to_owned();
+    public_path.push(".pub");
+    PathBuf::from(public_path)
+}
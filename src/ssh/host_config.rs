@@ -0,0 +1,610 @@
+use globset::Glob;
+use std::{
+    env,
+    fs::{read_dir, read_to_string},
+    io,
+    path::{Path, PathBuf},
+};
+use tracing::{debug, warn};
+
+use super::wildcard_match;
+
+/// Host blocks of an OpenSSH client config file, such as `~/.ssh/config`.
+#[derive(Default, Debug)]
+pub struct HostConfigs {
+    blocks: Vec<Block>,
+}
+
+/// Settings which apply to hosts matching any of the patterns, and none of the negated ones.
+#[derive(Debug)]
+struct Block {
+    /// none for settings before the first `Host`
+    patterns: Option<Vec<Pattern>>,
+    /// each with at least one argument
+    settings: Vec<(Keyword, Vec<String>)>,
+}
+
+#[derive(Clone, Debug)]
+struct Pattern {
+    negated: bool,
+    pattern: String,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Keyword {
+    HostName,
+    Port,
+    User,
+    IdentityFile,
+    ProxyJump,
+    UserKnownHostsFile,
+    GlobalKnownHostsFile,
+}
+
+/// Where and how to connect to a host, with everything from the config applied.
+#[derive(PartialEq, Eq, Debug)]
+pub struct HostParams {
+    /// as named in the config, e.g. an alias
+    pub host: String,
+    /// what to actually connect to
+    pub host_name: String,
+    pub port: u16,
+    pub user: Option<String>,
+    pub identity_files: Vec<PathBuf>,
+    /// hosts to go through, in order
+    pub proxy_jump: Vec<Jump>,
+    /// where host keys are looked up, and recorded in the first
+    pub user_known_hosts_files: Vec<PathBuf>,
+    /// where host keys are looked up too
+    pub global_known_hosts_files: Vec<PathBuf>,
+}
+
+/// A hop of `ProxyJump`, as `[user@]host[:port]`.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Jump {
+    pub user: Option<String>,
+    pub host: String,
+    pub port: Option<u16>,
+}
+
+const DEFAULT_USER_KNOWN_HOSTS_FILES: [&str; 2] = ["~/.ssh/known_hosts", "~/.ssh/known_hosts2"];
+const DEFAULT_GLOBAL_KNOWN_HOSTS_FILES: [&str; 2] =
+    ["/etc/ssh/ssh_known_hosts", "/etc/ssh/ssh_known_hosts2"];
+
+/// Deeper than this, `Include` is assumed to be recursive.
+const MAX_INCLUDE_DEPTH: usize = 16;
+
+impl HostConfigs {
+    /// Read `~/.ssh/config`, which if missing has no settings.
+    pub fn read_default() -> io::Result<Self> {
+        let Some(ssh_dir) = dirs::home_dir().map(|home| home.join(".ssh")) else {
+            return Ok(Self::default());
+        };
+        let mut configs = Self::default();
+        configs.read(&ssh_dir.join("config"), &ssh_dir, 0)?;
+        Ok(configs)
+    }
+
+    fn read(&mut self, path: &Path, ssh_dir: &Path, depth: usize) -> io::Result<()> {
+        match read_to_string(path) {
+            Ok(text) => {
+                debug!("reading ssh config {:?}", path);
+                self.parse(&text, ssh_dir, depth);
+                Ok(())
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn parse(&mut self, text: &str, ssh_dir: &Path, depth: usize) {
+        let mut skipping = false;
+        for line in text.lines() {
+            let Some((keyword, args)) = split_line(line) else {
+                continue;
+            };
+            match keyword.to_lowercase().as_str() {
+                "host" => {
+                    skipping = false;
+                    self.blocks.push(Block {
+                        patterns: Some(args.iter().map(|arg| Pattern::parse(arg)).collect()),
+                        settings: Vec::default(),
+                    });
+                }
+                "match" => {
+                    // only `Match all` can be evaluated without running commands and the like
+                    skipping = !args.iter().all(|arg| arg.eq_ignore_ascii_case("all"));
+                    if skipping {
+                        warn!("ignoring ssh config Match {}", args.join(" "));
+                    } else {
+                        self.blocks.push(Block {
+                            patterns: None,
+                            settings: Vec::default(),
+                        });
+                    }
+                }
+                "include" if !skipping => {
+                    if depth >= MAX_INCLUDE_DEPTH {
+                        warn!("ssh config Include nested too deeply");
+                        continue;
+                    }
+                    let n_blocks = self.blocks.len();
+                    for included in args.iter().flat_map(|arg| include_paths(arg, ssh_dir)) {
+                        if let Err(e) = self.read(&included, ssh_dir, depth + 1) {
+                            warn!("failed to read ssh config {:?}: {}", included, e);
+                        }
+                    }
+                    // any blocks started by included files end with them,
+                    // so what follows is back in the including block
+                    if self.blocks.len() > n_blocks {
+                        // before any block, settings apply to all hosts
+                        let patterns = n_blocks
+                            .checked_sub(1)
+                            .and_then(|last| self.blocks[last].patterns.clone());
+                        self.blocks.push(Block {
+                            patterns,
+                            settings: Vec::default(),
+                        });
+                    }
+                }
+                _ if skipping => (),
+                keyword => {
+                    let keyword = match keyword {
+                        "hostname" => Keyword::HostName,
+                        "port" => Keyword::Port,
+                        "user" => Keyword::User,
+                        "identityfile" => Keyword::IdentityFile,
+                        "proxyjump" => Keyword::ProxyJump,
+                        "userknownhostsfile" => Keyword::UserKnownHostsFile,
+                        "globalknownhostsfile" => Keyword::GlobalKnownHostsFile,
+                        // everything else is ssh's business, not ours
+                        _ => continue,
+                    };
+                    if args.is_empty() {
+                        continue;
+                    }
+                    if self.blocks.is_empty() {
+                        self.blocks.push(Block {
+                            patterns: None,
+                            settings: Vec::default(),
+                        });
+                    }
+                    self.blocks
+                        .last_mut()
+                        .unwrap()
+                        .settings
+                        .push((keyword, args));
+                }
+            }
+        }
+    }
+
+    /// Apply the config to `host`, where as with ssh, the first value found for each setting wins.
+    pub fn resolve(&self, host: &str) -> HostParams {
+        let host = host.to_lowercase();
+        let mut host_name = None;
+        let mut port = None;
+        let mut user = None;
+        let mut identity_files = Vec::default();
+        let mut proxy_jump = None;
+        let mut user_known_hosts_files = None;
+        let mut global_known_hosts_files = None;
+
+        for block in self.blocks.iter().filter(|block| block.matches(&host)) {
+            for (keyword, args) in block.settings.iter() {
+                let value = &args[0];
+                match keyword {
+                    Keyword::HostName if host_name.is_none() => {
+                        host_name = Some(expand_tokens(value, &host, None))
+                    }
+                    Keyword::Port if port.is_none() => {
+                        port = value.parse::<u16>().ok();
+                        if port.is_none() {
+                            warn!("ignoring ssh config Port {}", value);
+                        }
+                    }
+                    Keyword::User if user.is_none() => user = Some(value.clone()),
+                    Keyword::IdentityFile => identity_files.push(value.clone()),
+                    Keyword::ProxyJump if proxy_jump.is_none() => proxy_jump = Some(value.clone()),
+                    Keyword::UserKnownHostsFile if user_known_hosts_files.is_none() => {
+                        user_known_hosts_files = Some(args.clone())
+                    }
+                    Keyword::GlobalKnownHostsFile if global_known_hosts_files.is_none() => {
+                        global_known_hosts_files = Some(args.clone())
+                    }
+                    _ => (),
+                }
+            }
+        }
+
+        let host_name = host_name.unwrap_or_else(|| host.clone());
+        let expand_path = |file: &str| {
+            let file = expand_tokens(file, &host_name, user.as_deref());
+            PathBuf::from(shellexpand::tilde(&file).as_ref())
+        };
+        let identity_files = identity_files
+            .iter()
+            .map(|file| expand_path(file))
+            .collect();
+        let known_hosts_files = |files: Option<Vec<String>>, default: &[&str]| match files {
+            Some(files) if files == ["none"] => Vec::default(),
+            Some(files) => files.iter().map(|file| expand_path(file)).collect(),
+            None => default.iter().map(|file| expand_path(file)).collect(),
+        };
+        let user_known_hosts_files =
+            known_hosts_files(user_known_hosts_files, &DEFAULT_USER_KNOWN_HOSTS_FILES);
+        let global_known_hosts_files =
+            known_hosts_files(global_known_hosts_files, &DEFAULT_GLOBAL_KNOWN_HOSTS_FILES);
+        let proxy_jump = match proxy_jump.as_deref() {
+            None | Some("none") => Vec::default(),
+            Some(jumps) => jumps.split(',').filter_map(Jump::parse).collect(),
+        };
+
+        HostParams {
+            host,
+            host_name,
+            port: port.unwrap_or(22),
+            user,
+            identity_files,
+            proxy_jump,
+            user_known_hosts_files,
+            global_known_hosts_files,
+        }
+    }
+}
+
+impl Block {
+    fn matches(&self, host: &str) -> bool {
+        match self.patterns.as_ref() {
+            None => true,
+            Some(patterns) => {
+                let mut matched = false;
+                for pattern in patterns.iter() {
+                    if wildcard_match(&pattern.pattern, host) {
+                        if pattern.negated {
+                            return false;
+                        }
+                        matched = true;
+                    }
+                }
+                matched
+            }
+        }
+    }
+}
+
+impl Pattern {
+    fn parse(s: &str) -> Self {
+        match s.strip_prefix('!') {
+            Some(pattern) => Self {
+                negated: true,
+                pattern: pattern.to_lowercase(),
+            },
+            None => Self {
+                negated: false,
+                pattern: s.to_lowercase(),
+            },
+        }
+    }
+}
+
+impl Jump {
+    fn parse(s: &str) -> Option<Self> {
+        let s = s.trim();
+        let s = s.strip_prefix("ssh://").unwrap_or(s);
+        let (user, host_port) = match s.rsplit_once('@') {
+            Some((user, host_port)) => (Some(user.to_string()), host_port),
+            None => (None, s),
+        };
+        let (host, port) = match host_port.rsplit_once(':') {
+            Some((host, port)) => (host, Some(port.parse::<u16>().ok()?)),
+            None => (host_port, None),
+        };
+        (!host.is_empty()).then(|| Self {
+            user,
+            host: host.to_string(),
+            port,
+        })
+    }
+}
+
+/// Keyword and arguments, which may be separated by `=`, and quoted.
+fn split_line(line: &str) -> Option<(String, Vec<String>)> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+        return None;
+    }
+
+    let split_at = line.find(|c: char| c.is_whitespace() || c == '=')?;
+    let (keyword, rest) = line.split_at(split_at);
+    let rest = rest.trim_start();
+    let rest = rest.strip_prefix('=').unwrap_or(rest);
+
+    let mut args = Vec::new();
+    let mut arg = String::new();
+    let mut quoted = false;
+    let mut in_arg = false;
+    for c in rest.chars() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                in_arg = true;
+            }
+            c if c.is_whitespace() && !quoted => {
+                if in_arg {
+                    args.push(std::mem::take(&mut arg));
+                    in_arg = false;
+                }
+            }
+            c => {
+                arg.push(c);
+                in_arg = true;
+            }
+        }
+    }
+    if in_arg {
+        args.push(arg);
+    }
+
+    Some((keyword.to_string(), args))
+}
+
+/// Substitute `%h` host, `%r` remote user, `%u` local user, `%d` home directory and `%%`.
+fn expand_tokens(s: &str, host: &str, remote_user: Option<&str>) -> String {
+    let mut expanded = String::new();
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            expanded.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('h') => expanded.push_str(host),
+            Some('r') => expanded.push_str(remote_user.unwrap_or_default()),
+            Some('u') => expanded.push_str(&local_user()),
+            Some('d') => {
+                if let Some(home) = dirs::home_dir() {
+                    expanded.push_str(&home.to_string_lossy());
+                }
+            }
+            Some('%') => expanded.push('%'),
+            Some(c) => {
+                warn!("ignoring unsupported ssh config token %{}", c);
+            }
+            None => expanded.push('%'),
+        }
+    }
+    expanded
+}
+
+/// Who we are, which is who we connect as unless configured otherwise.
+pub fn local_user() -> String {
+    env::var("USER")
+        .or_else(|_| env::var("LOGNAME"))
+        .unwrap_or_default()
+}
+
+/// Files matched by an `Include` argument, relative paths being relative to `~/.ssh`.
+fn include_paths(arg: &str, ssh_dir: &Path) -> Vec<PathBuf> {
+    let path = ssh_dir.join(shellexpand::tilde(arg).as_ref());
+    let Some(file_name) = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+    else {
+        return Vec::default();
+    };
+    if !file_name.contains(['*', '?', '[']) {
+        return vec![path];
+    }
+
+    // wildcards are only supported in the final component
+    let (Some(dir), Ok(glob)) = (path.parent(), Glob::new(&file_name)) else {
+        return Vec::default();
+    };
+    let matcher = glob.compile_matcher();
+    let mut paths = read_dir(dir)
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok())
+                .map(|entry| entry.path())
+                .filter(|path| path.file_name().is_some_and(|name| matcher.is_match(name)))
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    paths.sort();
+    paths
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+
+    const CONFIG: &str = r#"
+Host build*  !build-old
+    HostName %h.cluster.example.com
+    User builder
+    IdentityFile ~/.ssh/id_build
+
+Host bastion
+    HostName bastion.example.com
+    Port 2222
+
+Host inner
+    HostName=10.1.2.3
+    ProxyJump alice@bastion,gateway:2022
+
+Match exec "test -f /nonexistent"
+    User ignored
+
+Host *
+    User everyone
+    IdentityFile "~/.ssh/id_%r"
+    ProxyJump none
+"#;
+
+    fn params(
+        host: &str,
+        host_name: &str,
+        port: u16,
+        user: &str,
+        proxy_jump: Vec<Jump>,
+    ) -> HostParams {
+        HostParams {
+            host: host.to_string(),
+            host_name: host_name.to_string(),
+            port,
+            user: Some(user.to_string()),
+            identity_files: Vec::default(),
+            proxy_jump,
+            user_known_hosts_files: Vec::default(),
+            global_known_hosts_files: Vec::default(),
+        }
+    }
+
+    fn jump(user: Option<&str>, host: &str, port: Option<u16>) -> Jump {
+        Jump {
+            user: user.map(str::to_string),
+            host: host.to_string(),
+            port,
+        }
+    }
+
+    #[test_case("build7", params("build7", "build7.cluster.example.com", 22, "builder", vec![]); "pattern with token")]
+    #[test_case("build-old", params("build-old", "build-old", 22, "everyone", vec![]); "negated")]
+    #[test_case("bastion", params("bastion", "bastion.example.com", 2222, "everyone", vec![]); "port")]
+    #[test_case("inner", params("inner", "10.1.2.3", 22, "everyone", vec![jump(Some("alice"), "bastion", None), jump(None, "gateway", Some(2022))]); "proxy jump")]
+    #[test_case("Elsewhere", params("elsewhere", "elsewhere", 22, "everyone", vec![]); "defaults")]
+    fn test_resolve(host: &str, expected: HostParams) {
+        let mut configs = HostConfigs::default();
+        configs.parse(CONFIG, Path::new("/nonexistent"), 0);
+        let resolved = configs.resolve(host);
+        assert_eq!(
+            HostParams {
+                identity_files: Vec::default(),
+                user_known_hosts_files: Vec::default(),
+                global_known_hosts_files: Vec::default(),
+                ..resolved
+            },
+            expected
+        );
+    }
+
+    #[test]
+    fn test_identity_files() {
+        let mut configs = HostConfigs::default();
+        configs.parse(CONFIG, Path::new("/nonexistent"), 0);
+        let home = dirs::home_dir().unwrap();
+        assert_eq!(
+            configs.resolve("build1").identity_files,
+            vec![home.join(".ssh/id_build"), home.join(".ssh/id_builder")]
+        );
+    }
+
+    #[test]
+    fn test_known_hosts_files() {
+        let mut configs = HostConfigs::default();
+        configs.parse(
+            "Host bastion\n  UserKnownHostsFile ~/.ssh/known_%h \"/etc/ssh/team hosts\"\n  GlobalKnownHostsFile none\n",
+            Path::new("/nonexistent"),
+            0,
+        );
+        let home = dirs::home_dir().unwrap();
+
+        let bastion = configs.resolve("bastion");
+        assert_eq!(
+            bastion.user_known_hosts_files,
+            vec![
+                home.join(".ssh/known_bastion"),
+                PathBuf::from("/etc/ssh/team hosts")
+            ]
+        );
+        assert!(bastion.global_known_hosts_files.is_empty());
+
+        let other = configs.resolve("other");
+        assert_eq!(
+            other.user_known_hosts_files,
+            vec![
+                home.join(".ssh/known_hosts"),
+                home.join(".ssh/known_hosts2")
+            ]
+        );
+        assert_eq!(
+            other.global_known_hosts_files,
+            vec![
+                PathBuf::from("/etc/ssh/ssh_known_hosts"),
+                PathBuf::from("/etc/ssh/ssh_known_hosts2")
+            ]
+        );
+    }
+
+    #[test_case("ssh://bob@jump:22", Some(jump(Some("bob"), "jump", Some(22))))]
+    #[test_case("jump", Some(jump(None, "jump", None)))]
+    #[test_case("jump:port", None; "bad port")]
+    fn test_jump_parse(s: &str, expected: Option<Jump>) {
+        assert_eq!(Jump::parse(s), expected);
+    }
+
+    /// Write the files to a temporary `~/.ssh`, and read its `config`.
+    fn read_included(files: &[(&str, &str)]) -> HostConfigs {
+        let ssh_dir = tempfile::TempDir::new().unwrap();
+        for (name, text) in files {
+            let path = ssh_dir.path().join(name);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, text).unwrap();
+        }
+        let mut configs = HostConfigs::default();
+        configs
+            .read(&ssh_dir.path().join("config"), ssh_dir.path(), 0)
+            .unwrap();
+        configs
+    }
+
+    #[test]
+    fn test_include() {
+        let configs = read_included(&[
+            ("config", "Include conf.d/*.conf\nPort 2200\n"),
+            (
+                "conf.d/alpha.conf",
+                "Host alpha\n  HostName alpha.example.com\n  Include nested\n  Port 2222\n",
+            ),
+            ("conf.d/beta.conf", "Host beta\n  User beta-user\n"),
+            ("conf.d/ignored.txt", "Host *\n  User ignored\n"),
+            ("nested", "User nested-user\n"),
+        ]);
+
+        let alpha = configs.resolve("alpha");
+        assert_eq!(
+            (alpha.host_name.as_str(), alpha.port, alpha.user.as_deref()),
+            ("alpha.example.com", 2222, Some("nested-user"))
+        );
+        let beta = configs.resolve("beta");
+        assert_eq!(
+            (beta.host_name.as_str(), beta.port, beta.user.as_deref()),
+            ("beta", 2200, Some("beta-user"))
+        );
+        // settings after the Include apply to all hosts, not just the last included Host
+        let gamma = configs.resolve("gamma");
+        assert_eq!((gamma.port, gamma.user), (2200, None));
+    }
+
+    #[test]
+    fn test_include_within_host() {
+        let configs = read_included(&[
+            ("config", "Host alpha\n  Include extra\n  User alpha-user\n"),
+            (
+                "extra",
+                "HostName alpha.example.com\nHost beta\n  Port 2222\n",
+            ),
+        ]);
+
+        let alpha = configs.resolve("alpha");
+        assert_eq!(
+            (alpha.host_name.as_str(), alpha.port, alpha.user.as_deref()),
+            ("alpha.example.com", 22, Some("alpha-user"))
+        );
+        let beta = configs.resolve("beta");
+        assert_eq!((beta.port, beta.user), (2222, None));
+    }
+}
@@ -0,0 +1,407 @@
+use base64::{engine::general_purpose::STANDARD, Engine};
+use hmac::{Hmac, Mac};
+use sha1::Sha1;
+use std::{
+    borrow::Cow,
+    fmt::Display,
+    fs::{read_to_string, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+use tracing::warn;
+
+use super::wildcard_match;
+
+/// Entries of OpenSSH `known_hosts` files, the user's and the global ones.
+///
+/// Host certificates are out of scope, russh being unable to negotiate them, so `@cert-authority`
+/// entries can't vouch for a host, and a host they match is refused rather than trusted on first use.
+#[derive(Default, Debug)]
+pub struct KnownHosts {
+    /// where keys trusted on first use are recorded, the first `UserKnownHostsFile`
+    user_path: Option<PathBuf>,
+    /// every file looked in
+    paths: Vec<PathBuf>,
+    entries: Vec<Entry>,
+}
+
+#[derive(Debug)]
+struct Entry {
+    marker: Option<Marker>,
+    patterns: Vec<Pattern>,
+    key_type: String,
+    key: Vec<u8>,
+    at: Location,
+}
+
+/// Where an entry is, for telling the user.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Location {
+    pub path: PathBuf,
+    pub line: usize,
+}
+
+#[derive(PartialEq, Eq, Debug)]
+enum Marker {
+    CertAuthority,
+    Revoked,
+}
+
+#[derive(Debug)]
+enum Pattern {
+    /// possibly with `*` and `?` wildcards
+    Plain { negated: bool, pattern: String },
+    /// `|1|salt|hash`, as written with `HashKnownHosts yes`
+    Hashed { salt: Vec<u8>, hash: Vec<u8> },
+}
+
+/// What `known_hosts` says about the key presented by a host.
+#[derive(PartialEq, Eq, Debug)]
+pub enum Verdict {
+    Trusted,
+    /// the host has no key of this type
+    Unknown,
+    /// the host is known only by a certificate authority, whose certificates can't be checked
+    CertAuthorityOnly {
+        at: Location,
+    },
+    /// the host is known by a different key of the same type
+    Mismatch {
+        at: Location,
+    },
+    Revoked {
+        at: Location,
+    },
+}
+
+impl KnownHosts {
+    /// Read the user's files, then the global ones, as `ssh` does, skipping any missing.
+    pub fn read(user_paths: &[PathBuf], global_paths: &[PathBuf]) -> Self {
+        let mut known_hosts = Self {
+            user_path: user_paths.first().cloned(),
+            ..Self::default()
+        };
+        for path in user_paths.iter().chain(global_paths) {
+            match read_to_string(path) {
+                Ok(text) => known_hosts.parse(&text, path),
+                Err(e) if e.kind() == io::ErrorKind::NotFound => (),
+                // as for ssh, which goes on without it
+                Err(e) => warn!("failed to read {:?}: {}", path, e),
+            }
+            known_hosts.paths.push(path.clone());
+        }
+        known_hosts
+    }
+
+    fn parse(&mut self, text: &str, path: &Path) {
+        for (i, line) in text.lines().enumerate() {
+            let at = Location {
+                path: path.to_path_buf(),
+                line: i + 1,
+            };
+            match Entry::parse(line, at) {
+                Some(entry) => self.entries.push(entry),
+                None if !is_blank_or_comment(line) => {
+                    warn!("ignoring {:?} line {}", path, i + 1)
+                }
+                None => (),
+            }
+        }
+    }
+
+    /// Every file looked in, for telling the user.
+    pub fn paths(&self) -> &[PathBuf] {
+        &self.paths
+    }
+
+    /// Check the key presented by `host`, where `key` is the SSH wire encoding, beginning with its type.
+    pub fn verify(&self, host: &str, port: u16, key: &[u8]) -> Verdict {
+        // as ssh does, including before hashing
+        let host = host.to_lowercase();
+        let host_port = host_port(&host, port);
+        let key_type = key_type(key);
+        let matching = self
+            .entries
+            .iter()
+            .filter(|entry| entry.matches(&host_port))
+            .collect::<Vec<_>>();
+
+        if let Some(entry) = matching
+            .iter()
+            .find(|entry| entry.marker == Some(Marker::Revoked) && entry.key == key)
+        {
+            return Verdict::Revoked {
+                at: entry.at.clone(),
+            };
+        }
+
+        let host_keys = matching
+            .iter()
+            .filter(|entry| entry.marker.is_none())
+            .collect::<Vec<_>>();
+        if host_keys.iter().any(|entry| entry.key == key) {
+            Verdict::Trusted
+        } else if let Some(entry) = host_keys
+            .iter()
+            .find(|entry| Some(entry.key_type.as_str()) == key_type)
+        {
+            Verdict::Mismatch {
+                at: entry.at.clone(),
+            }
+        } else if let Some(entry) = matching
+            .iter()
+            .find(|entry| entry.marker == Some(Marker::CertAuthority))
+        {
+            Verdict::CertAuthorityOnly {
+                at: entry.at.clone(),
+            }
+        } else {
+            Verdict::Unknown
+        }
+    }
+
+    /// Record the key for the host in the first user file, as `ssh` does on first use.
+    pub fn append(&self, host: &str, port: u16, key: &[u8]) -> io::Result<()> {
+        let key_type = key_type(key)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "malformed host key"))?;
+        let path = self.user_path.as_ref().ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                "no UserKnownHostsFile to record host key in",
+            )
+        })?;
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+
+        let existing = read_to_string(path).unwrap_or_default();
+        let mut file = OpenOptions::new().append(true).create(true).open(path)?;
+        if !existing.is_empty() && !existing.ends_with('\n') {
+            file.write_all(b"\n")?;
+        }
+        writeln!(
+            file,
+            "{} {} {}",
+            host_port(host, port),
+            key_type,
+            STANDARD.encode(key)
+        )
+    }
+}
+
+impl Entry {
+    fn parse(line: &str, at: Location) -> Option<Self> {
+        let mut fields = line.split_whitespace();
+        let mut first = fields.next()?;
+        let marker = match first {
+            "@cert-authority" => Some(Marker::CertAuthority),
+            "@revoked" => Some(Marker::Revoked),
+            _ if first.starts_with('#') => return None,
+            _ if first.starts_with('@') => return None,
+            _ => None,
+        };
+        if marker.is_some() {
+            first = fields.next()?;
+        }
+
+        let patterns = first
+            .split(',')
+            .map(Pattern::parse)
+            .collect::<Option<Vec<_>>>()?;
+        let key_type = fields.next()?.to_string();
+        let key = STANDARD.decode(fields.next()?).ok()?;
+
+        Some(Self {
+            marker,
+            patterns,
+            key_type,
+            key,
+            at,
+        })
+    }
+
+    /// Whether any pattern matches, and no negated one does.
+    fn matches(&self, host_port: &str) -> bool {
+        let mut matched = false;
+        for pattern in self.patterns.iter() {
+            match pattern {
+                Pattern::Plain { negated, pattern } => {
+                    if wildcard_match(pattern, host_port) {
+                        if *negated {
+                            return false;
+                        }
+                        matched = true;
+                    }
+                }
+                Pattern::Hashed { salt, hash } => {
+                    if let Ok(hmac) = Hmac::<Sha1>::new_from_slice(salt) {
+                        if hmac
+                            .chain_update(host_port.as_bytes())
+                            .verify_slice(hash)
+                            .is_ok()
+                        {
+                            matched = true;
+                        }
+                    }
+                }
+            }
+        }
+        matched
+    }
+}
+
+impl Pattern {
+    fn parse(s: &str) -> Option<Self> {
+        if let Some(hashed) = s.strip_prefix("|1|") {
+            let (salt, hash) = hashed.split_once('|')?;
+            Some(Pattern::Hashed {
+                salt: STANDARD.decode(salt).ok()?,
+                hash: STANDARD.decode(hash).ok()?,
+            })
+        } else {
+            let (negated, pattern) = match s.strip_prefix('!') {
+                Some(pattern) => (true, pattern),
+                None => (false, s),
+            };
+            Some(Pattern::Plain {
+                negated,
+                pattern: pattern.to_lowercase(),
+            })
+        }
+    }
+}
+
+impl Display for Location {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.path.display(), self.line)
+    }
+}
+
+/// How hosts are named in `known_hosts`, with the port only if it isn't the default.
+fn host_port(host: &str, port: u16) -> Cow<'_, str> {
+    if port == 22 {
+        Cow::Borrowed(host)
+    } else {
+        Cow::Owned(format!("[{}]:{}", host, port))
+    }
+}
+
+/// The type name at the start of a key's wire encoding.
+fn key_type(key: &[u8]) -> Option<&str> {
+    let len = u32::from_be_bytes(key.get(..4)?.try_into().ok()?) as usize;
+    std::str::from_utf8(key.get(4..4 + len)?).ok()
+}
+
+fn is_blank_or_comment(line: &str) -> bool {
+    let line = line.trim_start();
+    line.is_empty() || line.starts_with('#')
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+
+    /// wire encoding of a key of the given type, with arbitrary key material
+    fn key(key_type: &str, material: &[u8]) -> Vec<u8> {
+        let mut key = (key_type.len() as u32).to_be_bytes().to_vec();
+        key.extend(key_type.as_bytes());
+        key.extend(material);
+        key
+    }
+
+    fn line(hosts: &str, key_type: &str, material: &[u8]) -> String {
+        format!(
+            "{} {} {}",
+            hosts,
+            key_type,
+            STANDARD.encode(key(key_type, material))
+        )
+    }
+
+    /// `|1|salt|hash` for `host`, as `ssh-keygen -H` would write it
+    fn hashed(host: &str) -> String {
+        let salt = b"01234567890123456789";
+        let hash = Hmac::<Sha1>::new_from_slice(salt)
+            .unwrap()
+            .chain_update(host.as_bytes())
+            .finalize()
+            .into_bytes();
+        format!("|1|{}|{}", STANDARD.encode(salt), STANDARD.encode(hash))
+    }
+
+    fn at(line: usize) -> Location {
+        Location {
+            path: PathBuf::from("known_hosts"),
+            line,
+        }
+    }
+
+    #[test_case("build1", 22, b"A", Verdict::Trusted; "plain")]
+    #[test_case("BUILD1", 22, b"A", Verdict::Trusted; "case insensitive")]
+    #[test_case("build1", 22, b"B", Verdict::Mismatch { at: at(2) }; "changed key")]
+    #[test_case("build1", 2222, b"A", Verdict::Unknown; "other port")]
+    #[test_case("build2", 2222, b"C", Verdict::Trusted; "non-default port")]
+    #[test_case("hashed.example.com", 22, b"D", Verdict::Trusted; "hashed")]
+    #[test_case("Hashed.Example.com", 22, b"D", Verdict::Trusted; "hashed case insensitive")]
+    #[test_case("hashed.example.com", 22, b"E", Verdict::Mismatch { at: at(4) }; "hashed changed key")]
+    #[test_case("node7.cluster", 22, b"F", Verdict::Trusted; "wildcard")]
+    #[test_case("login.cluster", 22, b"F", Verdict::Unknown; "negated")]
+    #[test_case("node7.cluster", 22, b"R", Verdict::Revoked { at: at(6) }; "revoked")]
+    #[test_case("ca.example.org", 22, b"G", Verdict::CertAuthorityOnly { at: at(7) }; "cert authority only")]
+    #[test_case("nowhere", 22, b"A", Verdict::Unknown; "unknown")]
+    fn test_verify(host: &str, port: u16, material: &[u8], expected: Verdict) {
+        let text = [
+            "# comment".to_string(),
+            line("build1,10.0.0.1", "ssh-ed25519", b"A"),
+            line("[build2]:2222", "ssh-ed25519", b"C"),
+            line(&hashed("hashed.example.com"), "ssh-ed25519", b"D"),
+            line("*.cluster,!login.cluster", "ssh-ed25519", b"F"),
+            format!("@revoked {}", line("*", "ssh-ed25519", b"R")),
+            format!(
+                "@cert-authority {}",
+                line("*.example.org", "ssh-ed25519", b"G")
+            ),
+            line("build1", "ssh-rsa", b"H"),
+        ]
+        .join("\n");
+        let mut known_hosts = KnownHosts::default();
+        known_hosts.parse(&text, Path::new("known_hosts"));
+        assert_eq!(
+            known_hosts.verify(host, port, &key("ssh-ed25519", material)),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_read_user_and_global() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let user_path = dir.path().join("ssh/known_hosts");
+        let global_path = dir.path().join("ssh_known_hosts");
+        std::fs::write(&global_path, line("build1", "ssh-ed25519", b"A") + "\n").unwrap();
+        let known_hosts = KnownHosts::read(
+            &[user_path.clone(), dir.path().join("missing")],
+            std::slice::from_ref(&global_path),
+        );
+
+        assert_eq!(
+            known_hosts.verify("build1", 22, &key("ssh-ed25519", b"B")),
+            Verdict::Mismatch {
+                at: Location {
+                    path: global_path,
+                    line: 1
+                }
+            }
+        );
+
+        // recorded in the first user file, even though it was missing
+        let new_key = key("ssh-ed25519", b"C");
+        known_hosts.append("build2", 22, &new_key).unwrap();
+        let known_hosts = KnownHosts::read(&[user_path], &[]);
+        assert_eq!(known_hosts.verify("build2", 22, &new_key), Verdict::Trusted);
+        assert!(KnownHosts::read(&[], &[])
+            .append("build2", 22, &new_key)
+            .is_err());
+    }
+}
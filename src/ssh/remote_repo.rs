@@ -0,0 +1,375 @@
+use gix::{
+    bstr::{BStr, ByteSlice},
+    config::{file::Metadata, File, Source},
+};
+use std::{collections::HashMap, path::PathBuf};
+use tracing::{debug, warn};
+
+use super::{path_join, Connection, GitDir};
+use crate::model::{Head, LocalRepo, Remote, RepoStatus};
+
+/// Limit on following symbolic refs, in case of a cycle.
+const MAX_SYMREF_DEPTH: usize = 5;
+
+impl Connection {
+    /// Read remotes and status of the repo, as far as is possible from its files alone.
+    ///
+    /// Without the object database, ahead/behind is only known when level with the upstream,
+    /// and the worktree isn't examined, so is never reported dirty or untracked.
+    pub async fn repo(&self, git_dir: &GitDir) -> LocalRepo {
+        let dir = if git_dir.bare {
+            git_dir.path.clone()
+        } else {
+            path_join(&git_dir.path, ".git")
+        };
+        let files = RepoFiles {
+            connection: self,
+            dir: &dir,
+        };
+
+        let config = files.config().await;
+        let remotes = remotes(&config);
+        let status = files.status(&config).await;
+        LocalRepo::new(PathBuf::from(&git_dir.path), remotes, status)
+    }
+
+    /// The contents of the file, or None if it is missing or unreadable.
+    async fn read_file(&self, path: &str) -> Option<String> {
+        let _permit = self.permit().await;
+        match self.sftp_session.read(path).await {
+            Ok(bytes) => Some(String::from_utf8_lossy(&bytes).into_owned()),
+            Err(e) => {
+                debug!("failed to read {}: {}", path, e);
+                None
+            }
+        }
+    }
+}
+
+/// Files of the git dir of one repo.
+struct RepoFiles<'a> {
+    connection: &'a Connection,
+    dir: &'a str,
+}
+
+impl RepoFiles<'_> {
+    async fn read(&self, name: &str) -> Option<String> {
+        self.connection.read_file(&path_join(self.dir, name)).await
+    }
+
+    /// The user's global config on the host, overridden by the repo's own, as git reads them.
+    async fn config(&self) -> File<'static> {
+        // relative to the home directory, as SFTP paths are
+        let global = self.connection.read_file(".gitconfig").await;
+        let local = self.read("config").await;
+
+        let mut config = File::default();
+        for (text, source) in [(global, Source::User), (local, Source::Local)] {
+            if let Some(text) = text {
+                match File::from_bytes_owned(
+                    &mut text.into_bytes(),
+                    Metadata::from(source),
+                    Default::default(),
+                ) {
+                    Ok(file) => {
+                        config.append(file);
+                    }
+                    Err(e) => warn!("failed to parse {:?} config in {}: {}", source, self.dir, e),
+                }
+            }
+        }
+        config
+    }
+
+    async fn status(&self, config: &File<'_>) -> RepoStatus {
+        let packed_refs = self
+            .read("packed-refs")
+            .await
+            .map(|text| packed_refs(&text))
+            .unwrap_or_default();
+
+        let head = match self.read("HEAD").await {
+            Some(text) => parse_head(&text),
+            None => {
+                warn!("no HEAD in {}", self.dir);
+                Head::Unknown
+            }
+        };
+
+        let mut ahead_behind = None;
+        let head = match head {
+            Head::Branch(branch) => {
+                let tip = self
+                    .resolve(&format!("refs/heads/{}", branch), &packed_refs)
+                    .await;
+                match tip {
+                    Some(tip) => {
+                        if let Some(upstream) = tracking_ref(config, &branch) {
+                            if self.resolve(&upstream, &packed_refs).await == Some(tip) {
+                                ahead_behind = Some((0, 0));
+                            }
+                        }
+                        Head::Branch(branch)
+                    }
+                    None => Head::Unborn(branch),
+                }
+            }
+            head => head,
+        };
+
+        let stashes = self
+            .read("logs/refs/stash")
+            .await
+            .map(|text| text.lines().filter(|line| !line.is_empty()).count())
+            .unwrap_or(0);
+
+        RepoStatus {
+            head,
+            dirty: false,
+            untracked: false,
+            stashes,
+            ahead_behind,
+        }
+    }
+
+    /// The object id of the ref, loose refs taking precedence over packed ones, as in git.
+    async fn resolve(&self, name: &str, packed_refs: &HashMap<String, String>) -> Option<String> {
+        let mut name = name.to_string();
+        for _ in 0..MAX_SYMREF_DEPTH {
+            match self.read(&name).await {
+                Some(text) => match text.trim().strip_prefix("ref:") {
+                    Some(target) => name = target.trim().to_string(),
+                    None => return Some(text.trim().to_string()),
+                },
+                None => return packed_refs.get(&name).cloned(),
+            }
+        }
+        None
+    }
+}
+
+fn remotes(config: &File<'_>) -> Vec<Remote> {
+    let mut names = config
+        .sections_by_name("remote")
+        .into_iter()
+        .flatten()
+        .filter_map(|section| section.header().subsection_name().map(BStr::to_string))
+        .collect::<Vec<_>>();
+    names.sort();
+    names.dedup();
+
+    let rewrites = UrlRewrites::new(config);
+    names
+        .into_iter()
+        .map(|name| {
+            let configured = |key| {
+                config
+                    .string_by("remote", Some(name.as_bytes().as_bstr()), key)
+                    .map(|value| value.to_string())
+            };
+            let url = configured("url");
+            let push_url = configured("pushurl");
+
+            let fetch_url = url.as_deref().map(|url| rewrites.fetch(url));
+            let rewritten_push_url = match (&push_url, &url) {
+                (Some(push_url), _) => Some(rewrites.fetch(push_url)),
+                (None, Some(url)) => Some(rewrites.push(url)),
+                (None, None) => None,
+            };
+            Remote::new(&name, fetch_url, rewritten_push_url).with_configured(url, push_url)
+        })
+        .collect()
+}
+
+/// `url.<base>.insteadOf` and `url.<base>.pushInsteadOf`, as (prefix, base) pairs.
+struct UrlRewrites {
+    instead_of: Vec<(String, String)>,
+    push_instead_of: Vec<(String, String)>,
+}
+
+impl UrlRewrites {
+    fn new(config: &File<'_>) -> Self {
+        let mut instead_of = Vec::new();
+        let mut push_instead_of = Vec::new();
+        for section in config.sections_by_name("url").into_iter().flatten() {
+            let Some(base) = section.header().subsection_name() else {
+                continue;
+            };
+            for (key, rewrites) in [
+                ("insteadOf", &mut instead_of),
+                ("pushInsteadOf", &mut push_instead_of),
+            ] {
+                rewrites.extend(
+                    section
+                        .values(key)
+                        .into_iter()
+                        .map(|prefix| (prefix.to_string(), base.to_string())),
+                );
+            }
+        }
+        Self {
+            instead_of,
+            push_instead_of,
+        }
+    }
+
+    fn fetch(&self, url: &str) -> String {
+        rewrite(&self.instead_of, url).unwrap_or_else(|| url.to_string())
+    }
+
+    /// For a remote without a push URL, where `pushInsteadOf` takes precedence over `insteadOf`.
+    fn push(&self, url: &str) -> String {
+        rewrite(&self.push_instead_of, url).unwrap_or_else(|| self.fetch(url))
+    }
+}
+
+/// Rewrite the URL by the longest matching prefix, as git does.
+fn rewrite(rewrites: &[(String, String)], url: &str) -> Option<String> {
+    rewrites
+        .iter()
+        .filter(|(prefix, _)| url.starts_with(prefix.as_str()))
+        .max_by_key(|(prefix, _)| prefix.len())
+        .map(|(prefix, base)| format!("{}{}", base, &url[prefix.len()..]))
+}
+
+/// HEAD as a branch, or detached, leaving whether the branch is unborn to be checked.
+fn parse_head(text: &str) -> Head {
+    let text = text.trim();
+    match text.strip_prefix("ref:") {
+        Some(target) => {
+            let target = target.trim();
+            Head::Branch(
+                target
+                    .strip_prefix("refs/heads/")
+                    .unwrap_or(target)
+                    .to_string(),
+            )
+        }
+        None if text.len() >= 7 && text.bytes().all(|b| b.is_ascii_hexdigit()) => {
+            Head::Detached(text[..7].to_string())
+        }
+        None => Head::Unknown,
+    }
+}
+
+/// Object ids of refs by full name, ignoring peeled tags.
+fn packed_refs(text: &str) -> HashMap<String, String> {
+    text.lines()
+        .filter(|line| !line.starts_with('#') && !line.starts_with('^'))
+        .filter_map(|line| {
+            let (id, name) = line.split_once(' ')?;
+            Some((name.trim().to_string(), id.to_string()))
+        })
+        .collect()
+}
+
+/// Full name of the remote-tracking ref of the branch's upstream, assuming the default refspec.
+fn tracking_ref(config: &File<'_>, branch: &str) -> Option<String> {
+    let subsection = Some(branch.as_bytes().as_bstr());
+    let remote = config.string_by("branch", subsection, "remote")?;
+    let merge = config.string_by("branch", subsection, "merge")?;
+    if remote.as_ref() == ".".as_bytes().as_bstr() {
+        // tracking a local branch
+        Some(merge.to_string())
+    } else {
+        let merge = merge.to_string();
+        let upstream_branch = merge.strip_prefix("refs/heads/").unwrap_or(&merge);
+        Some(format!("refs/remotes/{}/{}", remote, upstream_branch))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+
+    const CONFIG: &str = r#"
+[url "git@github.com:"]
+    insteadOf = gh:
+    pushInsteadOf = https://github.com/
+[url "git@github.com:acme/"]
+    insteadOf = gh:acme/
+[remote "origin"]
+    url = gh:acme/widget.git
+    fetch = +refs/heads/*:refs/remotes/origin/*
+[remote "upstream"]
+    url = https://github.com/widgets/widget.git
+[remote "mirror"]
+    url = https://example.com/widget.git
+    pushurl = gh:me/widget.git
+[branch "main"]
+    remote = origin
+    merge = refs/heads/main
+[branch "topic"]
+    remote = .
+    merge = refs/heads/main
+"#;
+
+    fn config() -> File<'static> {
+        File::from_bytes_owned(
+            &mut CONFIG.as_bytes().to_vec(),
+            Metadata::from(Source::Local),
+            Default::default(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_remotes() {
+        let remotes = remotes(&config());
+        let urls = remotes
+            .iter()
+            .map(|remote| (remote.name(), remote.fetch_url(), remote.push_url()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            urls,
+            vec![
+                (
+                    "mirror",
+                    Some("https://example.com/widget.git"),
+                    Some("git@github.com:me/widget.git")
+                ),
+                (
+                    "origin",
+                    Some("git@github.com:acme/widget.git"),
+                    Some("git@github.com:acme/widget.git")
+                ),
+                (
+                    "upstream",
+                    Some("https://github.com/widgets/widget.git"),
+                    Some("git@github.com:widgets/widget.git")
+                ),
+            ]
+        );
+    }
+
+    #[test_case("main", Some("refs/remotes/origin/main"); "remote")]
+    #[test_case("topic", Some("refs/heads/main"); "local")]
+    #[test_case("other", None; "none")]
+    fn test_tracking_ref(branch: &str, expected: Option<&str>) {
+        assert_eq!(tracking_ref(&config(), branch).as_deref(), expected);
+    }
+
+    #[test_case("ref: refs/heads/main\n", Head::Branch("main".to_string()); "branch")]
+    #[test_case("ref: refs/heads/feature/x\n", Head::Branch("feature/x".to_string()); "nested branch")]
+    #[test_case("0123456789abcdef0123456789abcdef01234567\n", Head::Detached("0123456".to_string()); "detached")]
+    #[test_case("garbage\n", Head::Unknown; "unknown")]
+    fn test_parse_head(text: &str, expected: Head) {
+        assert_eq!(parse_head(text), expected);
+    }
+
+    #[test]
+    fn test_packed_refs() {
+        let text = "# pack-refs with: peeled fully-peeled sorted \n\
+            1111111111111111111111111111111111111111 refs/heads/main\n\
+            2222222222222222222222222222222222222222 refs/tags/v1\n\
+            ^3333333333333333333333333333333333333333\n";
+        let refs = packed_refs(text);
+        assert_eq!(refs.len(), 2);
+        assert_eq!(
+            refs.get("refs/heads/main").map(String::as_str),
+            Some("1111111111111111111111111111111111111111")
+        );
+    }
+}
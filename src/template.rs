@@ -39,6 +39,17 @@ where
     }
 }
 
+/// Format each of the args, where `%f` is substituted by the path of a repo, as for
+/// the commands configured to run on repos.
+pub fn format_path_args<I, S>(args: I, path: &str) -> Result<Vec<String>, Error>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+{
+    let values = HashMap::from([('f', path)]);
+    args.into_iter().map(|arg| format(arg, &values)).collect()
+}
+
 #[cfg(test)]
 mod tests {
     use std::collections::HashMap;
@@ -60,6 +71,18 @@ mod tests {
         let values = HashMap::from_iter(values);
         assert_eq!(format(format_str, &values), expected.map(|s| s.to_string()));
     }
+
+    #[test]
+    fn test_format_path_args() {
+        assert_eq!(
+            format_path_args(["--repo=%f", "status"], "/src/widget"),
+            Ok(vec!["--repo=/src/widget".to_string(), "status".to_string()])
+        );
+        assert_eq!(
+            format_path_args(["%d"], "/src/widget"),
+            Err(UnknownFormatCharacter('d'))
+        );
+    }
 }
 
 #[derive(PartialEq, Debug)]
@@ -0,0 +1,310 @@
+use chrono::Utc;
+use futures::StreamExt;
+use gitmoto::{
+    config::GitHubConfig,
+    github::{rate_limit::Budgets, Connection, Error},
+    model::GitHubRepo,
+};
+use serde_json::{json, Value};
+use std::time::{Duration, Instant};
+use wiremock::{
+    matchers::{header, method, path, query_param, query_param_is_missing},
+    Mock, MockServer, ResponseTemplate,
+};
+
+/// As for GitHub Enterprise, so that paths below the base URL are exercised.
+const API_PATH: &str = "/api/v3";
+
+fn repo_json(owner: &str, name: &str) -> Value {
+    json!({
+        "id": 1,
+        "name": name,
+        "full_name": format!("{}/{}", owner, name),
+        "owner": {
+            "login": owner,
+            "id": 2,
+            "node_id": "MDQ6VXNlcjI=",
+            "avatar_url": "https://example.com/avatar",
+            "gravatar_id": "",
+            "url": format!("https://example.com/users/{}", owner),
+            "html_url": format!("https://example.com/{}", owner),
+            "followers_url": "https://example.com/followers",
+            "following_url": "https://example.com/following",
+            "gists_url": "https://example.com/gists",
+            "starred_url": "https://example.com/starred",
+            "subscriptions_url": "https://example.com/subscriptions",
+            "organizations_url": "https://example.com/orgs",
+            "repos_url": "https://example.com/repos",
+            "events_url": "https://example.com/events",
+            "received_events_url": "https://example.com/received_events",
+            "type": "User",
+            "site_admin": false,
+        },
+        "url": format!("https://example.com/repos/{}/{}", owner, name),
+        "clone_url": format!("https://example.com/{}/{}.git", owner, name),
+        "ssh_url": format!("git@example.com:{}/{}.git", owner, name),
+        "fork": name.ends_with("-fork"),
+    })
+}
+
+fn config(server: &MockServer) -> GitHubConfig {
+    GitHubConfig {
+        base_url: Some(format!("{}{}", server.uri(), API_PATH)),
+        token: Some("gho_test".to_string()),
+        ..Default::default()
+    }
+}
+
+/// Without a token, or none if there's one in the environment, which would take precedence.
+fn unauthenticated_config(server: &MockServer, user: Option<&str>) -> Option<GitHubConfig> {
+    if std::env::var_os("GITHUB_TOKEN").is_some() {
+        eprintln!("GITHUB_TOKEN is set, so can't test without a token");
+        return None;
+    }
+    Some(GitHubConfig {
+        base_url: Some(format!("{}{}", server.uri(), API_PATH)),
+        user: user.map(str::to_string),
+        ..Default::default()
+    })
+}
+
+async fn list(server: &MockServer) -> Vec<Result<GitHubRepo, Error>> {
+    let connection = Connection::new(&config(server)).unwrap();
+    connection.repos(None).collect::<Vec<_>>().await
+}
+
+async fn full_names(connection: &Connection) -> Vec<String> {
+    connection
+        .repos(None)
+        .map(|repo| repo.unwrap().full_name)
+        .collect()
+        .await
+}
+
+/// The first page of the user's repos, with a link to the second.
+async fn mount_first_page(server: &MockServer) {
+    Mock::given(method("GET"))
+        .and(path(format!("{}/user/repos", API_PATH)))
+        .and(query_param("affiliation", "owner,organization_member"))
+        .and(query_param_is_missing("page"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .insert_header(
+                    "link",
+                    format!(
+                        "<{}{}/user/repos?affiliation=owner%2Corganization_member&per_page=100&page=2>; rel=\"next\"",
+                        server.uri(),
+                        API_PATH
+                    )
+                    .as_str(),
+                )
+                .set_body_json(json!([
+                    repo_json("me", "widget"),
+                    repo_json("acme", "gadget-fork"),
+                ])),
+        )
+        .expect(1)
+        .mount(server)
+        .await;
+}
+
+#[tokio::test]
+async fn test_lists_all_pages() {
+    let server = MockServer::start().await;
+    mount_first_page(&server).await;
+    Mock::given(method("GET"))
+        .and(path(format!("{}/user/repos", API_PATH)))
+        .and(query_param("page", "2"))
+        .respond_with(
+            ResponseTemplate::new(200).set_body_json(json!([repo_json("acme", "sprocket")])),
+        )
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let repos = list(&server)
+        .await
+        .into_iter()
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    assert_eq!(
+        repos
+            .iter()
+            .map(|repo| (repo.full_name.as_str(), repo.fork))
+            .collect::<Vec<_>>(),
+        vec![
+            ("me/widget", false),
+            ("acme/gadget-fork", true),
+            ("acme/sprocket", false)
+        ]
+    );
+    assert_eq!(
+        repos[0].clone_url.as_deref(),
+        Some("https://example.com/me/widget.git")
+    );
+}
+
+#[tokio::test]
+async fn test_api_error() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path(format!("{}/user/repos", API_PATH)))
+        .respond_with(ResponseTemplate::new(401).set_body_json(json!({
+            "message": "Bad credentials",
+            "documentation_url": "https://docs.github.com/rest",
+        })))
+        .mount(&server)
+        .await;
+
+    let repos = list(&server).await;
+    assert_eq!(repos.len(), 1);
+    match &repos[0] {
+        Err(Error::Api { status, message }) => {
+            assert_eq!((*status, message.as_str()), (401, "Bad credentials"))
+        }
+        other => panic!("expected API error, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn test_error_part_way() {
+    let server = MockServer::start().await;
+    mount_first_page(&server).await;
+    Mock::given(method("GET"))
+        .and(path(format!("{}/user/repos", API_PATH)))
+        .and(query_param("page", "2"))
+        .respond_with(ResponseTemplate::new(404).set_body_json(json!({ "message": "Not Found" })))
+        .mount(&server)
+        .await;
+
+    let repos = list(&server).await;
+    assert_eq!(repos.len(), 3);
+    assert!(repos[..2].iter().all(Result::is_ok));
+    assert!(matches!(repos[2], Err(Error::Api { status: 404, .. })));
+}
+
+#[test]
+fn test_bad_base_url() {
+    let config = GitHubConfig {
+        base_url: Some("not a url".to_string()),
+        token: Some("gho_test".to_string()),
+        ..Default::default()
+    };
+    assert!(matches!(
+        Connection::new(&config),
+        Err(Error::BaseUrl(url, _)) if url == "not a url"
+    ));
+}
+
+#[tokio::test]
+async fn test_unchanged_page_reused() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path(format!("{}/user/repos", API_PATH)))
+        .and(header("if-none-match", "\"v1\""))
+        .respond_with(ResponseTemplate::new(304))
+        .with_priority(1)
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path(format!("{}/user/repos", API_PATH)))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .insert_header("etag", "\"v1\"")
+                .set_body_json(json!([repo_json("me", "widget")])),
+        )
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let connection = Connection::new(&config(&server)).unwrap();
+    assert_eq!(full_names(&connection).await, vec!["me/widget"]);
+    assert_eq!(full_names(&connection).await, vec!["me/widget"]);
+}
+
+#[tokio::test]
+async fn test_backs_off_when_rate_limited() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path(format!("{}/user/repos", API_PATH)))
+        .respond_with(
+            ResponseTemplate::new(403)
+                .insert_header("retry-after", "1")
+                .set_body_json(json!({
+                    "message": "You have exceeded a secondary rate limit",
+                })),
+        )
+        .up_to_n_times(1)
+        .with_priority(1)
+        .mount(&server)
+        .await;
+    let reset = Utc::now().timestamp() + 3600;
+    Mock::given(method("GET"))
+        .and(path(format!("{}/user/repos", API_PATH)))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .insert_header("x-ratelimit-remaining", "4321")
+                .insert_header("x-ratelimit-reset", reset.to_string().as_str())
+                .set_body_json(json!([repo_json("me", "widget")])),
+        )
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let connection = Connection::new(&config(&server)).unwrap();
+    let start = Instant::now();
+    assert_eq!(full_names(&connection).await, vec!["me/widget"]);
+    assert!(start.elapsed() >= Duration::from_secs(1));
+
+    let mut budgets = Budgets::new();
+    connection.save_budget(&mut budgets);
+    let budget = budgets.values().next().unwrap();
+    assert_eq!((budget.remaining, budget.reset.timestamp()), (4321, reset));
+}
+
+#[tokio::test]
+async fn test_public_repos_without_token() {
+    let server = MockServer::start().await;
+    let Some(config) = unauthenticated_config(&server, Some("me")) else {
+        return;
+    };
+    for (route, body) in [
+        ("/users/me/repos", json!([repo_json("me", "widget")])),
+        ("/users/me/orgs", json!([{ "login": "acme" }])),
+        ("/orgs/acme/repos", json!([repo_json("acme", "gadget")])),
+    ] {
+        Mock::given(method("GET"))
+            .and(path(format!("{}{}", API_PATH, route)))
+            .respond_with(ResponseTemplate::new(200).set_body_json(body))
+            .expect(1)
+            .mount(&server)
+            .await;
+    }
+
+    let connection = Connection::new(&config).unwrap();
+    let full_names = connection
+        .repos(config.user.as_deref())
+        .map(|repo| repo.unwrap().full_name)
+        .collect::<Vec<_>>()
+        .await;
+    assert_eq!(full_names, vec!["me/widget", "acme/gadget"]);
+
+    let requests = server.received_requests().await.unwrap();
+    assert!(requests
+        .iter()
+        .all(|request| !request.headers.contains_key("authorization")));
+}
+
+#[tokio::test]
+async fn test_no_token_or_user() {
+    let server = MockServer::start().await;
+    let Some(config) = unauthenticated_config(&server, None) else {
+        return;
+    };
+
+    let connection = Connection::new(&config).unwrap();
+    let repos = connection.repos(None).collect::<Vec<_>>().await;
+    assert!(matches!(repos[..], [Err(Error::NoTokenOrUser)]));
+    assert!(server.received_requests().await.unwrap().is_empty());
+}